@@ -3,3 +3,23 @@ set FXC="%DXSDK_DIR%\Utilities\bin\x64\fxc.exe" -nologo
 if not exist data mkdir data
 %FXC% /T vs_4_0 /E Vertex /Fo data/vertex.fx shader/blend.hlsl
 %FXC% /T ps_4_0 /E Pixel /Fo data/pixel.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=0 /Fo data/pixel_0.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=1 /Fo data/pixel_1.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=2 /Fo data/pixel_2.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=3 /Fo data/pixel_3.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=4 /Fo data/pixel_4.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=5 /Fo data/pixel_5.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=6 /Fo data/pixel_6.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=7 /Fo data/pixel_7.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=8 /Fo data/pixel_8.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=9 /Fo data/pixel_9.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=10 /Fo data/pixel_10.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=11 /Fo data/pixel_11.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=12 /Fo data/pixel_12.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=13 /Fo data/pixel_13.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=14 /Fo data/pixel_14.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=15 /Fo data/pixel_15.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=16 /Fo data/pixel_16.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=17 /Fo data/pixel_17.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=18 /Fo data/pixel_18.fx shader/blend.hlsl
+%FXC% /T ps_4_0 /E Pixel /D FIXED_BLEND=19 /Fo data/pixel_19.fx shader/blend.hlsl
@@ -0,0 +1,54 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The readback and the GPU timer go through D3D11, which only Windows has. Elsewhere they
+// can't be created, so the export button is disabled and the timings stay empty.
+
+use gfx;
+
+pub enum TextureReader {}
+
+impl TextureReader {
+    pub fn new<R, T>(_: &gfx::handle::Texture<R, T>) -> Option<TextureReader>
+        where R: gfx::Resources
+    {
+        None
+    }
+
+    pub fn read_rgba8(&self) -> Result<(u32, u32, Vec<u8>), String> {
+        match *self {}
+    }
+}
+
+pub enum GpuTimer {}
+
+impl GpuTimer {
+    pub fn new<R, T>(_: &gfx::handle::Texture<R, T>) -> Option<GpuTimer>
+        where R: gfx::Resources
+    {
+        None
+    }
+
+    pub fn begin(&mut self, _: usize) {
+        match *self {}
+    }
+
+    pub fn end(&mut self) {
+        match *self {}
+    }
+
+    pub fn poll(&mut self) -> Option<(usize, f64)> {
+        match *self {}
+    }
+}
@@ -18,9 +18,16 @@ extern crate gfx_app;
 extern crate image;
 
 extern crate sciter;
+extern crate gfx_sciter_examples;
+
+// only the blend mode names are needed here, the equations are for the tests
+#[allow(dead_code)]
+mod reference;
+#[cfg(not(windows))]
+mod fallback;
 
 use std::any::Any;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::{Rc, Weak};
 
 type SciterHost = Rc<sciter::Host>;
@@ -34,6 +41,15 @@ struct View {
 
 pub use gfx::format::{Rgba8, Srgba8, DepthStencil};
 
+use reference::BLENDS;
+use gfx_sciter_examples::{path_from_url, to_number};
+#[cfg(windows)]
+use gfx_sciter_examples::readback::TextureReader;
+#[cfg(windows)]
+use gfx_sciter_examples::timer::GpuTimer;
+#[cfg(not(windows))]
+use fallback::{GpuTimer, TextureReader};
+
 gfx_vertex_struct!( Vertex {
     pos: [f32; 2] = "a_Pos",
     uv: [f32; 2] = "a_Uv",
@@ -50,44 +66,163 @@ impl Vertex {
 
 gfx_constant_struct!( Locals {
     blend: i32 = "u_Blend",
+    opacity: f32 = "u_Opacity",
+    mask: i32 = "u_Mask",
 });
 
+// `lena` is the image blended onto, `tint` the layer blended with it
 gfx_pipeline!( pipe {
     vbuf: gfx::VertexBuffer<Vertex> = (),
     lena: gfx::TextureSampler<[f32; 4]> = "t_Lena",
     tint: gfx::TextureSampler<[f32; 4]> = "t_Tint",
     blend: gfx::Global<i32> = "i_Blend",
+    opacity: gfx::Global<f32> = "f_Opacity",
+    use_mask: gfx::Global<i32> = "i_Mask",
+    mask: gfx::TextureSampler<[f32; 4]> = "t_Mask",
+    locals: gfx::ConstantBuffer<Locals> = "Locals",
+    out: gfx::RenderTarget<Rgba8> = "Target0",
+});
+
+// same as `pipe`, drawing the composited image to the screen with the "Normal" mode
+gfx_pipeline!( present {
+    vbuf: gfx::VertexBuffer<Vertex> = (),
+    lena: gfx::TextureSampler<[f32; 4]> = "t_Lena",
+    tint: gfx::TextureSampler<[f32; 4]> = "t_Tint",
+    blend: gfx::Global<i32> = "i_Blend",
+    opacity: gfx::Global<f32> = "f_Opacity",
+    use_mask: gfx::Global<i32> = "i_Mask",
+    mask: gfx::TextureSampler<[f32; 4]> = "t_Mask",
     locals: gfx::ConstantBuffer<Locals> = "Locals",
     out: gfx::RenderTarget<Srgba8> = "Target0",
 });
 
-fn load_texture<R, F>(factory: &mut F, data: &[u8])
-                -> Result<gfx::handle::ShaderResourceView<R, [f32; 4]>, String> where
-                R: gfx::Resources, F: gfx::Factory<R> {
+fn load_image(data: &[u8]) -> image::RgbaImage {
     use std::io::Cursor;
+    image::load(Cursor::new(data), image::PNG).unwrap().to_rgba()
+}
+
+fn create_texture<R, F>(factory: &mut F, img: &image::RgbaImage)
+                  -> Result<gfx::handle::ShaderResourceView<R, [f32; 4]>, String> where
+                  R: gfx::Resources, F: gfx::Factory<R> {
     use gfx::tex as t;
-    let img = image::load(Cursor::new(data), image::PNG).unwrap().to_rgba();
     let (width, height) = img.dimensions();
     let kind = t::Kind::D2(width as t::Size, height as t::Size, t::AaMode::Single);
     let (_, view) = factory.create_texture_const_u8::<Rgba8>(kind, &[&img]).unwrap();
     Ok(view)
 }
 
-const BLENDS: [&'static str; 9] = [
-    "Screen",
-    "Dodge",
-    "Burn",
-    "Overlay",
-    "Multiply",
-    "Add",
-    "Divide",
-    "Grain Extract",
-    "Grain Merge",
+// a soft disc in the middle of the image, where blending applies when the mask is enabled,
+// unless a mask image is given with `--mask`
+fn radial_mask(width: u32, height: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let dx = 2.0 * (x as f32 + 0.5) / width as f32 - 1.0;
+        let dy = 2.0 * (y as f32 + 0.5) / height as f32 - 1.0;
+        let r = (dx * dx + dy * dy).sqrt();
+        let v = (255.0 * (1.0 - (r - 0.5) / 0.3).max(0.0).min(1.0)) as u8;
+        image::Rgba([v, v, v, 255])
+    })
+}
+
+// Ways of selecting the blend equation: branching on a uniform in one program,
+// or one program per equation with the branch resolved at compile time.
+const STRATEGIES: [&'static str; 2] = [
+    "Uber-shader",
+    "Pipeline per technique",
+];
+const PER_TECHNIQUE: usize = 1;
+
+// per technique HLSL pixel shaders, built by compile.cmd with FIXED_BLEND defined
+const TECHNIQUE_PIXEL_FX: [&'static [u8]; 20] = [
+    include_bytes!("data/pixel_0.fx"),
+    include_bytes!("data/pixel_1.fx"),
+    include_bytes!("data/pixel_2.fx"),
+    include_bytes!("data/pixel_3.fx"),
+    include_bytes!("data/pixel_4.fx"),
+    include_bytes!("data/pixel_5.fx"),
+    include_bytes!("data/pixel_6.fx"),
+    include_bytes!("data/pixel_7.fx"),
+    include_bytes!("data/pixel_8.fx"),
+    include_bytes!("data/pixel_9.fx"),
+    include_bytes!("data/pixel_10.fx"),
+    include_bytes!("data/pixel_11.fx"),
+    include_bytes!("data/pixel_12.fx"),
+    include_bytes!("data/pixel_13.fx"),
+    include_bytes!("data/pixel_14.fx"),
+    include_bytes!("data/pixel_15.fx"),
+    include_bytes!("data/pixel_16.fx"),
+    include_bytes!("data/pixel_17.fx"),
+    include_bytes!("data/pixel_18.fx"),
+    include_bytes!("data/pixel_19.fx"),
 ];
 
+// inserts `#define name value` right after the `#version` line of a GLSL source
+fn glsl_define(source: &[u8], name: &str, value: usize) -> Vec<u8> {
+    let line_end = source.iter().position(|&b| b == b'\n').map_or(source.len(), |p| p + 1);
+    let mut out = source[..line_end].to_vec();
+    out.extend_from_slice(format!("#define {} {}\n", name, value).as_bytes());
+    out.extend_from_slice(&source[line_end..]);
+    out
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Timing {
+    // average GPU time of the layer draws in milliseconds with each strategy, while it is in use
+    layers_ms: [f64; 2],
+}
+
+// index of the "Normal" mode, which just replaces the image with the layer
+fn normal_blend() -> usize {
+    BLENDS.iter().position(|&b| b == "Normal").unwrap()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Layer {
+    name: String,
+    // index into the loaded images
+    image: usize,
+    blend: usize,
+    opacity: f32,
+    visible: bool,
+    masked: bool,
+}
+
+impl Layer {
+    fn new(name: &str, image: usize, blend: usize) -> Layer {
+        Layer {
+            name: name.to_string(),
+            image: image,
+            blend: blend,
+            opacity: 1.0,
+            visible: true,
+            masked: false,
+        }
+    }
+}
+
+struct ViewPair<R: gfx::Resources, T: gfx::format::Formatted> {
+    resource: gfx::handle::ShaderResourceView<R, T::View>,
+    target: gfx::handle::RenderTargetView<R, T>,
+}
+
 struct App<R: gfx::Resources>{
     bundle: pipe::Bundle<R>,
-    id: Rc<Cell<u8>>,
+    present: present::Bundle<R>,
+    // the images layers can use, textures can only be created at startup
+    images: Vec<gfx::handle::ShaderResourceView<R, [f32; 4]>>,
+    // the stack is composited back and forth between these, at the resolution of the first image
+    targets: [ViewPair<R, Rgba8>; 2],
+    // bottom layer first
+    layers: Rc<RefCell<Vec<Layer>>>,
+    // which of `targets` the last frame composited the stack into
+    result: Rc<Cell<usize>>,
+    // read `targets` back for exporting, empty if the backend can't
+    readers: Rc<Vec<TextureReader>>,
+    // one pipeline per blend equation, indexed like `BLENDS`
+    techniques: Vec<gfx::PipelineState<R, pipe::Meta>>,
+    strategy: Rc<Cell<usize>>,
+    timing: Rc<Cell<Timing>>,
+    // None if the backend has no GPU timer
+    timer: Option<GpuTimer>,
     view: Option<View>,
 }
 
@@ -120,10 +255,65 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
         ];
         let (vbuf, slice) = factory.create_vertex_buffer(&vertex_data);
 
-        let lena_texture = load_texture(&mut factory, &include_bytes!("image/lena.png")[..]).unwrap();
-        let tint_texture = load_texture(&mut factory, &include_bytes!("image/tint.png")[..]).unwrap();
+        // lena and tint, followed by the images given on the command line
+        let lena = load_image(&include_bytes!("image/lena.png")[..]);
+        let tint = load_image(&include_bytes!("image/tint.png")[..]);
+        let (width, height) = lena.dimensions();
+        let mut sources = vec![lena, tint];
+        let mut layers = vec![
+            Layer::new("lena", 0, normal_blend()),
+            Layer::new("tint", 1, 0),
+        ];
+        let mut mask = None;
+        let mut args = std::env::args().skip(1);
+        while let Some(path) = args.next() {
+            // `--mask file` replaces the soft disc the masked layers are limited to
+            if path == "--mask" {
+                if let Some(path) = args.next() {
+                    match image::open(&path) {
+                        Ok(img) => mask = Some(img.to_rgba()),
+                        Err(e) => println!("can't load mask '{}': {}", path, e),
+                    }
+                }
+                continue;
+            }
+            match image::open(&path) {
+                Ok(img) => {
+                    let name = std::path::Path::new(&path).file_name().unwrap().to_string_lossy().into_owned();
+                    layers.push(Layer::new(&name, sources.len(), normal_blend()));
+                    sources.push(img.to_rgba());
+                },
+                Err(e) => println!("can't load '{}': {}", path, e),
+            }
+        }
+        // the layers and the mask cover the stack, at the resolution of lena
+        let fit = |img: image::RgbaImage| {
+            if img.dimensions() == (width, height) {
+                img
+            } else {
+                image::imageops::resize(&img, width, height, image::FilterType::Triangle)
+            }
+        };
+        let images: Vec<_> = sources.into_iter()
+            .map(|img| create_texture(&mut factory, &fit(img)).unwrap())
+            .collect();
+
+        let mask = mask.map_or_else(|| radial_mask(width, height), &fit);
+        let mask_texture = create_texture(&mut factory, &mask).unwrap();
         let sampler = factory.create_sampler_linear();
 
+        let (texture0, srv0, rtv0) = factory.create_render_target(width as gfx::tex::Size, height as gfx::tex::Size).unwrap();
+        let (texture1, srv1, rtv1) = factory.create_render_target(width as gfx::tex::Size, height as gfx::tex::Size).unwrap();
+        let targets = [
+            ViewPair { resource: srv0, target: rtv0 },
+            ViewPair { resource: srv1, target: rtv1 },
+        ];
+        let timer = GpuTimer::new(&texture0);
+        let readers = match (TextureReader::new(&texture0), TextureReader::new(&texture1)) {
+            (Some(reader0), Some(reader1)) => vec![reader0, reader1],
+            _ => Vec::new(),
+        };
+
         let pso = factory.create_pipeline_simple(
             vs.select(init.backend).unwrap(),
             ps.select(init.backend).unwrap(),
@@ -132,25 +322,78 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
         ).unwrap();
 
         // we pass a integer to our shader to show what blending function we want
-        // it to use. normally you'd have a shader program per technique, but for
-        // the sake of simplicity we'll just branch on it inside the shader.
+        // it to use. normally you'd have a shader program per technique, which
+        // are built below, but the uber-shader just branches on it.
 
         // each index correspond to a conditional branch inside the shader
-        println!("Using '{}' blend equation", BLENDS[0]);
-        let cbuf = factory.create_constant_buffer(1);
+
+        // the GLSL variants get FIXED_BLEND injected, the HLSL ones are compiled offline
+        let uber_ps = ps.select(init.backend).unwrap();
+        let techniques = (0..BLENDS.len()).map(|mode| {
+            let source = if uber_ps.starts_with(b"#version") {
+                glsl_define(uber_ps, "FIXED_BLEND", mode)
+            } else {
+                let ps = gfx_app::shade::Source {
+                    hlsl_40: TECHNIQUE_PIXEL_FX[mode],
+                    .. gfx_app::shade::Source::empty()
+                };
+                ps.select(init.backend).unwrap().to_vec()
+            };
+            factory.create_pipeline_simple(
+                vs.select(init.backend).unwrap(),
+                &source,
+                gfx::state::CullFace::Nothing,
+                pipe::new()
+            ).unwrap()
+        }).collect();
 
         let data = pipe::Data {
-            vbuf: vbuf,
-            lena: (lena_texture, sampler.clone()),
-            tint: (tint_texture, sampler),
+            vbuf: vbuf.clone(),
+            lena: (targets[0].resource.clone(), sampler.clone()),
+            tint: (images[0].clone(), sampler.clone()),
             blend: 0,
-            locals: cbuf,
-            out: init.color,
+            opacity: 1.0,
+            use_mask: 0,
+            mask: (mask_texture.clone(), sampler.clone()),
+            locals: factory.create_constant_buffer(1),
+            out: targets[1].target.clone(),
+        };
+
+        let present = {
+            let pso = factory.create_pipeline_simple(
+                vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing,
+                present::new()
+            ).unwrap();
+
+            let data = present::Data {
+                vbuf: vbuf,
+                lena: (targets[0].resource.clone(), sampler.clone()),
+                tint: (targets[0].resource.clone(), sampler.clone()),
+                blend: normal_blend() as i32,
+                opacity: 1.0,
+                use_mask: 0,
+                mask: (mask_texture, sampler),
+                locals: factory.create_constant_buffer(1),
+                out: init.color,
+            };
+
+            present::bundle(slice.clone(), pso, data)
         };
 
         App {
             bundle: pipe::bundle(slice, pso, data),
-            id: Rc::new(Cell::new(0)),
+            present: present,
+            images: images,
+            targets: targets,
+            layers: Rc::new(RefCell::new(layers)),
+            result: Rc::new(Cell::new(0)),
+            readers: Rc::new(readers),
+            techniques: techniques,
+            strategy: Rc::new(Cell::new(0)),
+            timing: Rc::new(Cell::new(Timing::default())),
+            timer: timer,
             view: None,
         }
     }
@@ -174,22 +417,88 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
         let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
         self.view = Some(View { api: api, hwnd: host.get_hwnd() });
 
-        let handler = Handler { host: Rc::downgrade(&host.clone()), blend: self.id.clone() };
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          layers: self.layers.clone(),
+          result: self.result.clone(),
+          readers: self.readers.clone(),
+          strategy: self.strategy.clone(),
+          timing: self.timing.clone(),
+        };
         host.attach_handler(handler);
 
         let blends: sciter::Value = BLENDS.iter().cloned().collect();
         root.call_function("setupBlending", &[blends]).ok();
+        root.call_function("setupLayers", &[layers_to_value(&self.layers.borrow())]).ok();
+        let strategies: sciter::Value = STRATEGIES.iter().cloned().collect();
+        root.call_function("setupStrategies", &[strategies]).ok();
+        // only the D3D11 backend can read the targets back
+        root.call_function("setupExport", &[sciter::Value::from(!self.readers.is_empty())]).ok();
 
       } else {
         println!("oops: no root element!");
       }
     }
 
+    // the layers are composited here, so the GPU timer measures them alone
+    fn render_pre<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) -> bool {
+      let strategy = self.strategy.get();
+      if let Some(ref mut timer) = self.timer {
+        timer.begin(strategy);
+      }
+
+      // blend every visible layer onto the result of the layers below it
+      let mut src = 0;
+      encoder.clear(&self.targets[src].target, [0.0, 0.0, 0.0, 1.0]);
+      for layer in self.layers.borrow().iter().filter(|layer| layer.visible) {
+        let dst = 1 - src;
+        self.bundle.data.lena.0 = self.targets[src].resource.clone();
+        self.bundle.data.tint.0 = self.images[layer.image].clone();
+        self.bundle.data.out = self.targets[dst].target.clone();
+
+        // the GL shaders read the parameters from uniforms, the HLSL one from the constant buffer
+        self.bundle.data.blend = layer.blend as i32;
+        self.bundle.data.opacity = layer.opacity;
+        self.bundle.data.use_mask = layer.masked as i32;
+        let locals = Locals {
+          blend: layer.blend as i32,
+          opacity: layer.opacity,
+          mask: layer.masked as i32,
+        };
+        encoder.update_constant_buffer(&self.bundle.data.locals, &locals);
+        if strategy == PER_TECHNIQUE {
+          encoder.draw(&self.bundle.slice, &self.techniques[layer.blend], &self.bundle.data);
+        } else {
+          self.bundle.encode(encoder);
+        }
+        src = dst;
+      }
+      self.result.set(src);
+      return true;
+    }
+
     fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
-      let locals = Locals { blend: self.id.get() as i32 };
-      encoder.update_constant_buffer(&self.bundle.data.locals, &locals);
-      encoder.clear(&self.bundle.data.out, [0.0; 4]);
-      self.bundle.encode(encoder);
+      if let Some(ref mut timer) = self.timer {
+        timer.end();
+        if let Some((strategy, ms)) = timer.poll() {
+          let mut timing = self.timing.get();
+          let average = &mut timing.layers_ms[strategy];
+          *average = if *average == 0.0 { ms } else { 0.95 * *average + 0.05 * ms };
+          self.timing.set(timing);
+        }
+      }
+
+      let src = self.result.get();
+      let locals = Locals {
+        blend: self.present.data.blend,
+        opacity: 1.0,
+        mask: 0,
+      };
+      encoder.update_constant_buffer(&self.present.data.locals, &locals);
+      self.present.data.lena.0 = self.targets[src].resource.clone();
+      self.present.data.tint.0 = self.targets[src].resource.clone();
+      encoder.clear(&self.present.data.out, [0.0; 4]);
+      self.present.encode(encoder);
     }
 
     fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
@@ -216,7 +525,24 @@ impl<R: gfx::Resources> App<R> {
 #[allow(dead_code)]
 struct Handler {
   host: Weak<sciter::Host>,
-  blend: Rc<Cell<u8>>,
+  layers: Rc<RefCell<Vec<Layer>>>,
+  result: Rc<Cell<usize>>,
+  readers: Rc<Vec<TextureReader>>,
+  strategy: Rc<Cell<usize>>,
+  timing: Rc<Cell<Timing>>,
+}
+
+// `[[name, blend, opacity, visible, masked], ...]`, bottom layer first
+fn layers_to_value(layers: &[Layer]) -> sciter::Value {
+  layers.iter().map(|layer| {
+    vec![
+      sciter::Value::from(&layer.name[..]),
+      sciter::Value::from(layer.blend as i32),
+      sciter::Value::from(layer.opacity as f64),
+      sciter::Value::from(layer.visible),
+      sciter::Value::from(layer.masked),
+    ].into_iter().collect::<sciter::Value>()
+  }).collect()
 }
 
 impl sciter::EventHandler for Handler {
@@ -225,12 +551,60 @@ impl sciter::EventHandler for Handler {
   fn on_script_call(&mut self, root: sciter::HELEMENT, name: &str, args: &[sciter::Value]) -> Option<sciter::Value> {
     let ok = sciter::Value::from(true);
     match name {
-      "setBlending" => {
-        let id = args[0].to_int().unwrap();
-        println!("Using '{}' blend equation", BLENDS[id as usize]);
-        self.blend.set(id as u8);
+      "setLayer" => {
+        let mut layers = self.layers.borrow_mut();
+        let index = args[0].to_int().unwrap() as usize;
+        if let Some(layer) = layers.get_mut(index) {
+          layer.blend = (args[1].to_int().unwrap() as usize).min(BLENDS.len() - 1);
+          layer.opacity = (to_number(&args[2]).unwrap() as f32).max(0.0).min(1.0);
+          layer.visible = args[3].to_bool().unwrap();
+          layer.masked = args[4].to_bool().unwrap();
+          println!("Layer '{}' uses '{}' blend equation", layer.name, BLENDS[layer.blend]);
+        }
         Some(ok)
       },
+      "moveLayer" => {
+        let mut layers = self.layers.borrow_mut();
+        let from = args[0].to_int().unwrap() as usize;
+        let to = args[1].to_int().unwrap() as usize;
+        if from < layers.len() && to < layers.len() {
+          let layer = layers.remove(from);
+          layers.insert(to, layer);
+        }
+        Some(layers_to_value(&layers))
+      },
+      "setStrategy" => {
+        let id = (args[0].to_int().unwrap() as usize).min(STRATEGIES.len() - 1);
+        println!("Using '{}' strategy", STRATEGIES[id]);
+        self.strategy.set(id);
+        Some(ok)
+      },
+      "getTiming" => {
+        let timing = self.timing.get();
+        Some(timing.layers_ms.iter().cloned().collect())
+      },
+      "exportImage" => {
+        let path = args[0].as_string().unwrap();
+        let path = path_from_url(&path);
+        let path: &str = &path;
+        // the target holding the stack, as the last frame rendered it
+        let image = match self.readers.get(self.result.get()) {
+          Some(reader) => reader.read_rgba8().and_then(|(width, height, pixels)| {
+            image::RgbaImage::from_raw(width, height, pixels).ok_or("unexpected texture size".to_string())
+          }),
+          None => Err("the backend can't read textures back".to_string()),
+        };
+        match image.and_then(|image| image.save(path).map_err(|e| e.to_string())) {
+          Ok(_) => {
+            println!("image exported to {}", path);
+            Some(ok)
+          },
+          Err(e) => {
+            println!("unable to export the image to {}: {}", path, e);
+            Some(sciter::Value::from(false))
+          }
+        }
+      },
       _ => None,
     }
   }
@@ -0,0 +1,160 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// CPU reference of the blend equations in the shaders.
+//
+// `I` is the image (lena, or the layers composited so far) and `M` is the layer
+// blended onto it (tint), as in the GIMP documentation.
+// The mode indices are the ones of `BLENDS` and of the `#define`s in the shaders.
+
+use image::{Rgba, RgbaImage};
+
+pub const BLENDS: [&'static str; 20] = [
+    "Screen",
+    "Dodge",
+    "Burn",
+    "Overlay",
+    "Multiply",
+    "Add",
+    "Divide",
+    "Grain Extract",
+    "Grain Merge",
+    "Difference",
+    "Darken only",
+    "Lighten only",
+    "Soft light",
+    "Hard light",
+    "Hue",
+    "Saturation",
+    "Color",
+    "Value",
+    "Subtract",
+    "Normal",
+];
+
+fn rgb2hsv(c: [f32; 3]) -> [f32; 3] {
+    let hi = c[0].max(c[1]).max(c[2]);
+    let lo = c[0].min(c[1]).min(c[2]);
+    let d = hi - lo;
+    let h = if d <= 0.0 {
+        0.0
+    } else if hi == c[0] {
+        ((c[1] - c[2]) / d / 6.0 + 1.0) % 1.0
+    } else if hi == c[1] {
+        ((c[2] - c[0]) / d + 2.0) / 6.0
+    } else {
+        ((c[0] - c[1]) / d + 4.0) / 6.0
+    };
+    let s = if hi > 0.0 { d / hi } else { 0.0 };
+    [h, s, hi]
+}
+
+// fully saturated color of hue `h`
+fn hue_rgb(h: f32) -> [f32; 3] {
+    let channel = |k: f32| ((((h * 6.0 + k) % 6.0) - 3.0).abs() - 1.0).max(0.0).min(1.0);
+    [channel(0.0), channel(4.0), channel(2.0)]
+}
+
+fn hsv2rgb(c: [f32; 3]) -> [f32; 3] {
+    let rgb = hue_rgb(c[0]);
+    let mut out = [0.0; 3];
+    for i in 0..3 {
+        out[i] = c[2] * (1.0 + c[1] * (rgb[i] - 1.0));
+    }
+    out
+}
+
+fn rgb2hsl(c: [f32; 3]) -> [f32; 3] {
+    let hi = c[0].max(c[1]).max(c[2]);
+    let lo = c[0].min(c[1]).min(c[2]);
+    let l = 0.5 * (hi + lo);
+    let d = 1.0 - (2.0 * l - 1.0).abs();
+    let s = if d > 0.0 { (hi - lo) / d } else { 0.0 };
+    [rgb2hsv(c)[0], s, l]
+}
+
+fn hsl2rgb(c: [f32; 3]) -> [f32; 3] {
+    let rgb = hue_rgb(c[0]);
+    let chroma = c[1] * (1.0 - (2.0 * c[2] - 1.0).abs());
+    let mut out = [0.0; 3];
+    for i in 0..3 {
+        out[i] = c[2] + chroma * (rgb[i] - 0.5);
+    }
+    out
+}
+
+/// Blends one pixel, the result is not clamped.
+pub fn blend_pixel(mode: usize, i: [f32; 3], m: [f32; 3]) -> [f32; 3] {
+    // modes working on the HSV or HSL components
+    match mode {
+        // a layer without saturation has no hue, the image keeps its own
+        14 => { let (a, b) = (rgb2hsv(i), rgb2hsv(m)); return if b[1] > 0.0 { hsv2rgb([b[0], a[1], a[2]]) } else { i } },
+        15 => { let (a, b) = (rgb2hsv(i), rgb2hsv(m)); return hsv2rgb([a[0], b[1], a[2]]) },
+        16 => { let (a, b) = (rgb2hsl(i), rgb2hsl(m)); return hsl2rgb([b[0], b[1], a[2]]) },
+        17 => { let (a, b) = (rgb2hsv(i), rgb2hsv(m)); return hsv2rgb([a[0], a[1], b[2]]) },
+        _ => (),
+    }
+    let mut out = [0.0; 3];
+    for c in 0..3 {
+        let (i, m) = (i[c], m[c]);
+        out[c] = match mode {
+            0 => 1.0 - (1.0 - i) * (1.0 - m),
+            1 => i / (1.0 - m),
+            2 => 1.0 - (1.0 - i) / m,
+            3 => i * (i + 2.0 * m * (1.0 - i)),
+            4 => i * m,
+            5 => i + m,
+            6 => i / m,
+            7 => i - m + 0.5,
+            8 => i + m - 0.5,
+            9 => (i - m).abs(),
+            10 => i.min(m),
+            11 => i.max(m),
+            12 => ((1.0 - i) * m + (1.0 - (1.0 - i) * (1.0 - m))) * i,
+            13 => if m < 0.5 { 2.0 * m * i } else { 1.0 - (1.0 - 2.0 * (m - 0.5)) * (1.0 - i) },
+            18 => i - m,
+            19 => m,
+            _ => panic!("unknown blend mode {}", mode),
+        };
+    }
+    out
+}
+
+fn to_unorm(x: f32) -> u8 {
+    // NaN, from 0/0 in the division modes, ends up as 0
+    (x.max(0.0).min(1.0) * 255.0 + 0.5) as u8
+}
+
+/// Blends `tint` onto `lena`, all images being of the same size, as the shaders do.
+/// The clamped result is mixed over `lena` by `opacity`, times the red channel of the mask.
+pub fn blend(mode: usize, lena: &RgbaImage, tint: &RgbaImage, opacity: f32, mask: Option<&RgbaImage>) -> RgbaImage {
+    assert_eq!(lena.dimensions(), tint.dimensions());
+    let (width, height) = lena.dimensions();
+    RgbaImage::from_fn(width, height, |x, y| {
+        let (b, t) = (lena.get_pixel(x, y), tint.get_pixel(x, y));
+        let i = [b[0] as f32 / 255.0, b[1] as f32 / 255.0, b[2] as f32 / 255.0];
+        let m = [t[0] as f32 / 255.0, t[1] as f32 / 255.0, t[2] as f32 / 255.0];
+        let amount = match mask {
+            Some(mask) => opacity * mask.get_pixel(x, y)[0] as f32 / 255.0,
+            None => opacity,
+        };
+        let out = blend_pixel(mode, i, m);
+        let mut rgba = Rgba([0, 0, 0, 255]);
+        for c in 0..3 {
+            let value = out[c].max(0.0).min(1.0);
+            rgba.data[c] = to_unorm(i[c] + amount * (value - i[c]));
+        }
+        rgba
+    })
+}
@@ -7,6 +7,17 @@
 #define DIVIDE 6
 #define GRAIN_EXTRACT 7
 #define GRAIN_MERGE 8
+#define DIFFERENCE 9
+#define DARKEN_ONLY 10
+#define LIGHTEN_ONLY 11
+#define SOFT_LIGHT 12
+#define HARD_LIGHT 13
+#define HUE 14
+#define SATURATION 15
+#define COLOR 16
+#define VALUE 17
+#define SUBTRACT 18
+#define NORMAL 19
 
 struct VsOutput {
 	float4 pos: SV_Position;
@@ -23,24 +34,70 @@ VsOutput Vertex(float2 pos: a_Pos, float2 uv: a_Uv) {
 
 cbuffer Locals {
 	int u_Blend;
+	// how much of the blend result is mixed over lena
+	float u_Opacity;
+	// restricts the blending to the mask, when set
+	int u_Mask;
 };
 
-Texture2D<float> t_Lena;
+// the blend equation, fixed at compile time when FIXED_BLEND is defined
+// to build one program per technique
+#ifdef FIXED_BLEND
+#define BLEND FIXED_BLEND
+#else
+#define BLEND u_Blend
+#endif
+
+// the image blended onto: lena, or the layers composited so far
+Texture2D<float3> t_Lena;
 SamplerState t_Lena_;
+// the layer blended with it
 Texture2D<float3> t_Tint;
 SamplerState t_Tint_;
+Texture2D<float> t_Mask;
+SamplerState t_Mask_;
+
+// the component modes work in HSV, except Color which works in HSL,
+// as described in the GIMP documentation
+float3 rgb2hsv(float3 c) {
+	float4 K = float4(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
+	float4 p = lerp(float4(c.bg, K.wz), float4(c.gb, K.xy), step(c.b, c.g));
+	float4 q = lerp(float4(p.xyw, c.r), float4(c.r, p.yzx), step(p.x, c.r));
+	float d = q.x - min(q.w, q.y);
+	float e = 1.0e-10;
+	return float3(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x);
+}
+
+float3 hsv2rgb(float3 c) {
+	float4 K = float4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
+	float3 p = abs(frac(c.xxx + K.xyz) * 6.0 - K.www);
+	return c.z * lerp(K.xxx, saturate(p - K.xxx), c.y);
+}
+
+float3 rgb2hsl(float3 c) {
+	float hi = max(max(c.r, c.g), c.b);
+	float lo = min(min(c.r, c.g), c.b);
+	float l = 0.5 * (hi + lo);
+	float s = (hi - lo) / (1.0 - abs(2.0 * l - 1.0) + 1.0e-10);
+	return float3(rgb2hsv(c).x, s, l);
+}
+
+float3 hsl2rgb(float3 c) {
+	float3 rgb = saturate(abs(fmod(c.x * 6.0 + float3(0.0, 4.0, 2.0), 6.0) - 3.0) - 1.0);
+	return c.z + c.y * (rgb - 0.5) * (1.0 - abs(2.0 * c.z - 1.0));
+}
 
 float4 Pixel(VsOutput pin): SV_Target {
-	// we sample from both textures using the same uv coordinates. since our
-	// lena image is grayscale, we only get the first component.
+	// we sample from both textures using the same uv coordinates. the lena
+	// image is grayscale, but the layers composited onto it are not.
 	float3 lena = t_Lena.Sample(t_Lena_, pin.uv);
 	float3 tint = t_Tint.Sample(t_Tint_, pin.uv);
 
 	float3 result = 0.0.xxx;
 
-	// normally you'd have a shader program per technique, but for the sake of
-	// simplicity we'll just branch on it here.
-	switch (u_Blend) {
+	// with FIXED_BLEND, the compiler keeps a single branch. otherwise,
+	// we just branch on the constant here.
+	switch (BLEND) {
 		case SCREEN:
 			result = 1.0.xxx - (1.0.xxx - lena) * (1.0.xxx - tint);
 			break;
@@ -48,7 +105,7 @@ float4 Pixel(VsOutput pin): SV_Target {
 			result = lena / (1.0.xxx - tint);
 			break;
 		case BURN:
-			result = 1.0.xxx - ((1.0.xxx - lena) / lena);
+			result = 1.0.xxx - ((1.0.xxx - lena) / tint);
 			break;
 		case OVERLAY:
 			result = lena * (lena + (tint * 2) * (1.0.xxx - lena));
@@ -68,7 +125,46 @@ float4 Pixel(VsOutput pin): SV_Target {
 		case GRAIN_MERGE:
 			result = lena + tint - 0.5;
 			break;
+		case DIFFERENCE:
+			result = abs(lena - tint);
+			break;
+		case DARKEN_ONLY:
+			result = min(lena, tint);
+			break;
+		case LIGHTEN_ONLY:
+			result = max(lena, tint);
+			break;
+		case SOFT_LIGHT:
+			result = ((1.0.xxx - lena) * tint + (1.0.xxx - (1.0.xxx - lena) * (1.0.xxx - tint))) * lena;
+			break;
+		case HARD_LIGHT:
+			result = lerp(2.0 * tint * lena, 1.0.xxx - (1.0.xxx - 2.0 * (tint - 0.5)) * (1.0.xxx - lena), step(0.5, tint));
+			break;
+		case HUE:
+			// a layer without saturation has no hue, the image keeps its own
+			result = rgb2hsv(tint).y > 0.0 ? hsv2rgb(float3(rgb2hsv(tint).x, rgb2hsv(lena).yz)) : lena;
+			break;
+		case SATURATION:
+			result = hsv2rgb(float3(rgb2hsv(lena).x, rgb2hsv(tint).y, rgb2hsv(lena).z));
+			break;
+		case COLOR:
+			result = hsl2rgb(float3(rgb2hsl(tint).xy, rgb2hsl(lena).z));
+			break;
+		case VALUE:
+			result = hsv2rgb(float3(rgb2hsv(lena).xy, rgb2hsv(tint).z));
+			break;
+		case SUBTRACT:
+			result = lena - tint;
+			break;
+		case NORMAL:
+			result = tint;
+			break;
+	}
+
+	float amount = u_Opacity;
+	if (u_Mask != 0) {
+		amount *= t_Mask.Sample(t_Mask_, pin.uv);
 	}
 
-	return float4(result, 1.0);
+	return float4(lerp(lena, saturate(result), amount), 1.0);
 }
@@ -0,0 +1,249 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Chunked terrain with distance-based level of detail.
+//
+// The terrain grid is split into square chunks of `CHUNK_CELLS` cells. All chunks live in one
+// vertex buffer, each taking `CHUNK_VERTICES` vertices at full resolution, and they all share
+// one index buffer holding every index pattern a chunk can be drawn with. A chunk is drawn
+// with a slice into that index buffer and a base vertex pointing at its own vertices.
+//
+// Level `lod` uses every `2^lod`-th vertex. Neighbouring chunks never differ by more than one
+// level; when a neighbour is coarser, the vertices on the shared edge that the neighbour skips
+// are snapped to the previous kept one, so both sides of the edge describe the same line.
+//
+// Chunks whose bounding box is outside the camera frustum are skipped by `visible_slices`.
+// Every chunk still casts shadows, so `slices` keeps them all.
+
+use cgmath::{Matrix4, Point3, Vector4};
+use gfx;
+
+pub const CHUNK_CELLS: usize = 64;
+pub const CHUNK_VERTICES: usize = (CHUNK_CELLS + 1) * (CHUNK_CELLS + 1);
+pub const LOD_LEVELS: usize = 4;
+// a chunk switches to the next level every time the camera distance doubles,
+// starting at this many chunk widths
+const LOD_DISTANCE: f32 = 2.0;
+
+// edges facing a coarser neighbour, chunk rows go towards +Y
+const SOUTH: usize = 1;
+const NORTH: usize = 2;
+const WEST: usize = 4;
+const EAST: usize = 8;
+const NUM_EDGE_MASKS: usize = 16;
+
+// vertex index within a chunk at the grid position (x, y),
+// snapped along the edges that face a coarser neighbour
+fn stitched_index(x: usize, y: usize, step: usize, mask: usize) -> u16 {
+    let coarse = 2 * step;
+    let (mut x, mut y) = (x, y);
+    if (y == 0 && mask & SOUTH != 0) || (y == CHUNK_CELLS && mask & NORTH != 0) {
+        x -= x % coarse;
+    }
+    if (x == 0 && mask & WEST != 0) || (x == CHUNK_CELLS && mask & EAST != 0) {
+        y -= y % coarse;
+    }
+    (y * (CHUNK_CELLS + 1) + x) as u16
+}
+
+fn push_triangle(indices: &mut Vec<u16>, a: u16, b: u16, c: u16) {
+    // snapping collapses some of the edge triangles
+    if a != b && b != c && c != a {
+        indices.extend_from_slice(&[a, b, c]);
+    }
+}
+
+// appends the triangles of a chunk at the level `lod`, stitched along the edges in `mask`
+fn push_pattern(indices: &mut Vec<u16>, lod: usize, mask: usize) {
+    let step = 1 << lod;
+    let cells = CHUNK_CELLS / step;
+    for j in 0..cells {
+        for i in 0..cells {
+            let (x, y) = (i * step, j * step);
+            let a = stitched_index(x, y, step, mask);
+            let b = stitched_index(x + step, y, step, mask);
+            let c = stitched_index(x + step, y + step, step, mask);
+            let d = stitched_index(x, y + step, step, mask);
+            push_triangle(indices, a, b, c);
+            push_triangle(indices, c, d, a);
+        }
+    }
+}
+
+/// Builds the shared index data, along with the `(start, end)` range
+/// of every level and edge mask combination in it.
+pub fn generate_indices() -> (Vec<u16>, Vec<(u32, u32)>) {
+    let mut indices = Vec::new();
+    let mut ranges = Vec::with_capacity(LOD_LEVELS * NUM_EDGE_MASKS);
+    for lod in 0..LOD_LEVELS {
+        for mask in 0..NUM_EDGE_MASKS {
+            let start = indices.len() as u32;
+            push_pattern(&mut indices, lod, mask);
+            ranges.push((start, indices.len() as u32));
+        }
+    }
+    (indices, ranges)
+}
+
+pub struct ChunkedTerrain<R: gfx::Resources> {
+    indices: gfx::handle::Buffer<R, u16>,
+    ranges: Vec<(u32, u32)>,
+    chunks_per_side: usize,
+    // world space width of a chunk
+    chunk_size: f32,
+    // lowest and highest point of every chunk
+    heights: Vec<(f32, f32)>,
+    lods: Vec<usize>,
+    slices: Vec<gfx::Slice<R>>,
+    visible: Vec<bool>,
+}
+
+impl<R: gfx::Resources> ChunkedTerrain<R> {
+    pub fn new<F: gfx::Factory<R>>(factory: &mut F) -> ChunkedTerrain<R> {
+        let (index_data, ranges) = generate_indices();
+        let indices = factory.create_buffer_static(&index_data, gfx::BufferRole::Index, gfx::Bind::empty()).unwrap();
+        ChunkedTerrain {
+            indices: indices,
+            ranges: ranges,
+            chunks_per_side: 0,
+            chunk_size: 1.0,
+            heights: Vec::new(),
+            lods: Vec::new(),
+            slices: Vec::new(),
+            visible: Vec::new(),
+        }
+    }
+
+    /// Sets the grid layout, `world_size` being the width of the whole terrain and `heights`
+    /// the lowest and highest point of every chunk, in rows.
+    pub fn resize(&mut self, chunks_per_side: usize, world_size: f32, heights: Vec<(f32, f32)>) {
+        self.chunks_per_side = chunks_per_side;
+        self.chunk_size = world_size / chunks_per_side as f32;
+        self.heights = heights;
+        self.lods = vec![0; chunks_per_side * chunks_per_side];
+        self.visible = vec![true; chunks_per_side * chunks_per_side];
+    }
+
+    fn chunk_center(&self, cx: usize, cy: usize) -> Point3<f32> {
+        let half = 0.5 * self.chunk_size * self.chunks_per_side as f32;
+        Point3::new((cx as f32 + 0.5) * self.chunk_size - half,
+                    (cy as f32 + 0.5) * self.chunk_size - half,
+                    0.0)
+    }
+
+    // whether some of the bounding box of the chunk may be inside the clip volume
+    fn in_frustum(&self, cx: usize, cy: usize, view_proj: &Matrix4<f32>) -> bool {
+        let center = self.chunk_center(cx, cy);
+        let (low, high) = self.heights[cy * self.chunks_per_side + cx];
+        let half = 0.5 * self.chunk_size;
+        let corners: Vec<Vector4<f32>> = (0..8).map(|corner| *view_proj * Vector4::new(
+            center.x + if corner & 1 == 0 { -half } else { half },
+            center.y + if corner & 2 == 0 { -half } else { half },
+            if corner & 4 == 0 { low } else { high },
+            1.0)).collect();
+        // outside when all the corners are beyond the same clip plane
+        let beyond = |axis: usize, sign: f32| corners.iter().all(|c| sign * c[axis] > c.w);
+        !(0..3).any(|axis| beyond(axis, 1.0) || beyond(axis, -1.0))
+    }
+
+    /// Picks the level of every chunk for the given camera position, culls the chunks against
+    /// the frustum of `view_proj` and rebuilds the slices.
+    pub fn update(&mut self, camera: Point3<f32>, view_proj: Matrix4<f32>) {
+        let n = self.chunks_per_side;
+        for cy in 0..n {
+            for cx in 0..n {
+                self.visible[cy * n + cx] = self.in_frustum(cx, cy, &view_proj);
+            }
+        }
+        for cy in 0..n {
+            for cx in 0..n {
+                let center = self.chunk_center(cx, cy);
+                let (dx, dy, dz) = (camera.x - center.x, camera.y - center.y, camera.z - center.z);
+                let distance = (dx * dx + dy * dy + dz * dz).sqrt() / (LOD_DISTANCE * self.chunk_size);
+                let lod = if distance < 1.0 { 0 } else { distance.log2() as usize + 1 };
+                self.lods[cy * n + cx] = lod.min(LOD_LEVELS - 1);
+            }
+        }
+
+        // refine chunks until no two neighbours are more than one level apart
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for cy in 0..n {
+                for cx in 0..n {
+                    let finest = self.neighbours(cx, cy).iter()
+                        .filter_map(|&c| c)
+                        .map(|c| self.lods[c])
+                        .min();
+                    if let Some(finest) = finest {
+                        let lod = &mut self.lods[cy * n + cx];
+                        if *lod > finest + 1 {
+                            *lod = finest + 1;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.slices.clear();
+        for cy in 0..n {
+            for cx in 0..n {
+                let lod = self.lods[cy * n + cx];
+                let edges = [SOUTH, NORTH, WEST, EAST];
+                let mask = self.neighbours(cx, cy).iter().zip(edges.iter())
+                    .filter(|&(c, _)| c.map_or(false, |c| self.lods[c] > lod))
+                    .fold(0, |mask, (_, &edge)| mask | edge);
+                let (start, end) = self.ranges[lod * NUM_EDGE_MASKS + mask];
+                self.slices.push(gfx::Slice {
+                    start: start,
+                    end: end,
+                    base_vertex: ((cy * n + cx) * CHUNK_VERTICES) as gfx::VertexCount,
+                    instances: None,
+                    buffer: gfx::IndexBuffer::Index16(self.indices.clone()),
+                });
+            }
+        }
+    }
+
+    // south, north, west and east neighbour indices
+    fn neighbours(&self, cx: usize, cy: usize) -> [Option<usize>; 4] {
+        let n = self.chunks_per_side;
+        [
+            if cy > 0 { Some((cy - 1) * n + cx) } else { None },
+            if cy + 1 < n { Some((cy + 1) * n + cx) } else { None },
+            if cx > 0 { Some(cy * n + cx - 1) } else { None },
+            if cx + 1 < n { Some(cy * n + cx + 1) } else { None },
+        ]
+    }
+
+    /// One slice per chunk, as selected by the last `update`.
+    pub fn slices(&self) -> &[gfx::Slice<R>] {
+        &self.slices
+    }
+
+    /// The slices of the chunks in the frustum at the last `update`.
+    pub fn visible_slices(&self) -> Vec<&gfx::Slice<R>> {
+        self.slices.iter().zip(self.visible.iter()).filter(|&(_, &visible)| visible).map(|(slice, _)| slice).collect()
+    }
+
+    /// Full resolution indices of the whole terrain, for exporting.
+    pub fn full_indices(&self) -> Vec<u32> {
+        let mut chunk = Vec::new();
+        push_pattern(&mut chunk, 0, 0);
+        (0..self.chunks_per_side * self.chunks_per_side)
+            .flat_map(|c| chunk.iter().map(move |&i| (c * CHUNK_VERTICES) as u32 + i as u32))
+            .collect()
+    }
+}
@@ -5,7 +5,17 @@ if not exist data mkdir data
 %FXC% /T ps_4_0 /E TerrainPs /Fo data/terrain_ps.fx deferred.hlsl
 %FXC% /T vs_4_0 /E BlitVs /Fo data/blit_vs.fx deferred.hlsl
 %FXC% /T ps_4_0 /E BlitPs /Fo data/blit_ps.fx deferred.hlsl
+%FXC% /T vs_4_0 /E PostVs /Fo data/post_vs.fx deferred.hlsl
+%FXC% /T ps_4_0 /E BloomPs /Fo data/bloom_ps.fx deferred.hlsl
+%FXC% /T ps_4_0 /E TonemapPs /Fo data/tonemap_ps.fx deferred.hlsl
+%FXC% /T vs_4_0 /E ThumbnailVs /Fo data/thumbnail_vs.fx deferred.hlsl
+%FXC% /T ps_4_0 /E ThumbnailPs /Fo data/thumbnail_ps.fx deferred.hlsl
+%FXC% /T vs_4_0 /E ShadowVs /Fo data/shadow_vs.fx deferred.hlsl
+%FXC% /T ps_4_0 /E ShadowPs /Fo data/shadow_ps.fx deferred.hlsl
+%FXC% /T ps_4_0 /E SunPs /Fo data/sun_ps.fx deferred.hlsl
 %FXC% /T vs_4_0 /E LightVs /Fo data/light_vs.fx deferred.hlsl
 %FXC% /T ps_4_0 /E LightPs /Fo data/light_ps.fx deferred.hlsl
+%FXC% /T ps_4_0 /E SsaoPs /Fo data/ssao_ps.fx deferred.hlsl
+%FXC% /T ps_4_0 /E SsaoBlurPs /Fo data/ssao_blur_ps.fx deferred.hlsl
 %FXC% /T vs_4_0 /E EmitterVs /Fo data/emitter_vs.fx deferred.hlsl
 %FXC% /T ps_4_0 /E EmitterPs /Fo data/emitter_ps.fx deferred.hlsl
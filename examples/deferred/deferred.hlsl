@@ -57,6 +57,108 @@ float4 BlitPs(float4 pos: SV_Position): SV_Target {
 	return t_BlitTex.Load(int3(pos.xy, 0));
 }
 
+// Post processing programs
+
+struct PostVarying {
+	float4 pos: SV_Position;
+	float2 uv: TEXCOORD;
+};
+
+PostVarying PostVs(int2 pos: a_Pos) {
+	PostVarying output = {
+		float4(pos, 0.0, 1.0),
+		float2(0.5 + 0.5 * pos.x, 0.5 - 0.5 * pos.y),
+	};
+	return output;
+}
+
+cbuffer BloomLocals {
+	float4 BloomParams: u_BloomParams;
+};
+
+Texture2D<float4> t_Source;
+SamplerState t_Source_;
+
+static const float BlurWeights[5] = { 0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216 };
+
+float4 BloomPs(PostVarying In): SV_Target {
+	if (BloomParams.y < 0.5) {
+		// the linear sampler averages 2x2 texels of the source
+		float3 color = t_Source.Sample(t_Source_, In.uv).rgb;
+		float lum = dot(color, float3(0.2126, 0.7152, 0.0722));
+		return float4(color * max(0.0, lum - BloomParams.x) / max(lum, 0.0001), 1.0);
+	}
+	float2 dir = BloomParams.y < 1.5 ? float2(BloomParams.z, 0.0) : float2(0.0, BloomParams.w);
+	float3 sum = BlurWeights[0] * t_Source.Sample(t_Source_, In.uv).rgb;
+	for (int i = 1; i < 5; ++i) {
+		sum += BlurWeights[i] * t_Source.Sample(t_Source_, In.uv + i * dir).rgb;
+		sum += BlurWeights[i] * t_Source.Sample(t_Source_, In.uv - i * dir).rgb;
+	}
+	return float4(sum, 1.0);
+}
+
+cbuffer TonemapLocals {
+	float4 Tonemap: u_Tonemap;
+};
+
+Texture2D<float4> t_Hdr;
+SamplerState t_Hdr_;
+Texture2D<float4> t_Bloom;
+SamplerState t_Bloom_;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+float3 Aces(float3 x) {
+	return saturate((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14));
+}
+
+float4 TonemapPs(PostVarying In): SV_Target {
+	float3 color = t_Hdr.Sample(t_Hdr_, In.uv).rgb + Tonemap.z * t_Bloom.Sample(t_Bloom_, In.uv).rgb;
+	color *= Tonemap.x;
+	if (Tonemap.y > 1.5) {
+		color = Aces(color);
+	} else if (Tonemap.y > 0.5) {
+		color = color / (1.0 + color);
+	} else {
+		color = saturate(color);
+	}
+	return float4(color, 1.0);
+}
+
+// Thumbnail program
+
+cbuffer ThumbnailLocals {
+	float4 ThumbnailRect: u_Rect;
+};
+
+PostVarying ThumbnailVs(int2 pos: a_Pos) {
+	// the fullscreen triangle, scaled down to cover the box
+	float2 t = 0.5 + 0.5 * float2(pos);
+	PostVarying output = {
+		float4(ThumbnailRect.xy + t * ThumbnailRect.zw, 0.0, 1.0),
+		float2(t.x, 1.0 - t.y),
+	};
+	return output;
+}
+
+float4 ThumbnailPs(PostVarying In): SV_Target {
+	clip(In.uv);
+	clip(1.0 - In.uv);
+	return float4(t_Source.Sample(t_Source_, In.uv).rgb, 1.0);
+}
+
+// Shadow program
+
+cbuffer ShadowLocals {
+	float4x4 ShadowTransform: u_LightTransform;
+};
+
+float4 ShadowVs(float3 pos: a_Pos): SV_Position {
+	return mul(ShadowTransform, float4(pos, 1.0));
+}
+
+void ShadowPs() {
+}
+
 // common parts
 
 cbuffer CubeLocals {
@@ -83,6 +185,7 @@ struct LightVarying {
 Texture2D<float4> t_Position;
 Texture2D<float4> t_Normal;
 Texture2D<float4> t_Diffuse;
+Texture2D<float4> t_Occlusion;
 
 LightVarying LightVs(int3 pos: a_Pos, uint inst_id: SV_InstanceID) {
 	float3 lpos = offs[inst_id].xyz;
@@ -110,10 +213,128 @@ float4 LightPs(LightVarying In): SV_Target {
 	float dist_sq = dot(light - pos, light - pos);
 	float scale = max(0.0, 1.0-dist_sq * CamPosAndRadius.w);
 
-	float3 res_color = d * diffuse + s;
+	float ao = t_Occlusion.Load(itc).r;
+
+	float3 res_color = d * ao * diffuse + s;
 	return float4(scale*res_color, 1.0);
 }
 
+// Sun program
+
+cbuffer SunLocals {
+	float4x4 SunTransform: u_LightTransform;
+	float4 SunDirection: u_SunDirection;
+	float4 SunColor: u_SunColor;
+	float4 ShadowParams: u_ShadowParams;
+};
+
+Texture2D<float> t_Shadow;
+SamplerState t_Shadow_;
+
+float ShadowFactor(float3 pos) {
+	float4 light_pos = mul(SunTransform, float4(pos, 1.0));
+	float3 coord = light_pos.xyz / light_pos.w;
+	float2 uv = float2(0.5 + 0.5 * coord.x, 0.5 - 0.5 * coord.y);
+	// 3x3 percentage closer filtering
+	float lit = 0.0;
+	for (int y = -1; y <= 1; ++y) {
+		for (int x = -1; x <= 1; ++x) {
+			float depth = t_Shadow.Sample(t_Shadow_, uv + float2(x, y) * ShadowParams.y);
+			lit += coord.z - ShadowParams.x <= depth ? 1.0 : 0.0;
+		}
+	}
+	return lit / 9.0;
+}
+
+float4 SunPs(float4 pos: SV_Position): SV_Target {
+	int3 itc = int3(pos.xy, 0);
+	float3 wpos = t_Position.Load(itc).xyz;
+	float3 normal = t_Normal.Load(itc).xyz;
+	float3 diffuse = t_Diffuse.Load(itc).xyz;
+
+	// nothing was rendered here
+	if (dot(normal, normal) < 0.5)
+		discard;
+
+	float3 n = normalize(normal);
+	float d = max(0.0, dot(n, SunDirection.xyz));
+	float lit = d > 0.0 ? ShadowFactor(wpos) : 0.0;
+	float ao = t_Occlusion.Load(itc).r;
+
+	return float4((SunColor.w + lit * d * SunColor.rgb) * ao * diffuse, 1.0);
+}
+
+// Ambient occlusion programs
+
+cbuffer SsaoLocals {
+	float4x4 SsaoView: u_View;
+	float4x4 SsaoProj: u_Proj;
+	float4 SsaoParams: u_SsaoParams;
+};
+
+#define MAX_SSAO_SAMPLES	64
+cbuffer u_SsaoKernel {
+	float4 kernel[MAX_SSAO_SAMPLES];
+};
+
+// random rotation of the kernel, tiled over 4x4 pixels like a small noise texture
+float Hash(float2 p) {
+	return frac(sin(dot(p, float2(12.9898, 78.233))) * 43758.5453);
+}
+
+float4 SsaoPs(float4 pos: SV_Position): SV_Target {
+	int3 itc = int3(pos.xy, 0);
+	float3 normal = t_Normal.Load(itc).xyz;
+	// nothing was rendered here
+	if (dot(normal, normal) < 0.5)
+		return 1.0.xxxx;
+
+	float radius = SsaoParams.x;
+	int count = int(SsaoParams.y);
+	float3 vpos = mul(SsaoView, float4(t_Position.Load(itc).xyz, 1.0)).xyz;
+	float3 n = normalize(mul(SsaoView, float4(normal, 0.0)).xyz);
+
+	float angle = 6.2831853 * Hash(fmod(floor(pos.xy), 4.0));
+	float3 rvec = float3(cos(angle), sin(angle), 0.0);
+	float3 t = normalize(rvec - n * dot(rvec, n));
+	float3 b = cross(n, t);
+
+	uint width, height;
+	t_Position.GetDimensions(width, height);
+	int2 size = int2(width, height);
+	float occlusion = 0.0;
+	for (int i = 0; i < count; ++i) {
+		float3 k = kernel[i].xyz;
+		float3 sample_pos = vpos + radius * (k.x * t + k.y * b + k.z * n);
+		float4 clip = mul(SsaoProj, float4(sample_pos, 1.0));
+		float2 uv = float2(0.5 + 0.5 * clip.x / clip.w, 0.5 - 0.5 * clip.y / clip.w);
+		int2 stc = int2(uv * size);
+		if (any(stc < 0) || any(stc >= size))
+			continue;
+		float depth = mul(SsaoView, float4(t_Position.Load(int3(stc, 0)).xyz, 1.0)).z;
+		float range = smoothstep(0.0, 1.0, radius / abs(vpos.z - depth));
+		occlusion += (depth >= sample_pos.z + 0.05 ? 1.0 : 0.0) * range;
+	}
+
+	float ao = saturate(1.0 - SsaoParams.z * occlusion / max(float(count), 1.0));
+	return float4(ao.xxx, 1.0);
+}
+
+// 4x4 box blur, matching the period of the kernel rotation noise
+float4 SsaoBlurPs(float4 pos: SV_Position): SV_Target {
+	uint width, height;
+	t_Occlusion.GetDimensions(width, height);
+	int2 itc = int2(pos.xy);
+	float sum = 0.0;
+	for (int y = -2; y < 2; ++y) {
+		for (int x = -2; x < 2; ++x) {
+			int2 tc = clamp(itc + int2(x, y), int2(0, 0), int2(width, height) - 1);
+			sum += t_Occlusion.Load(int3(tc, 0)).r;
+		}
+	}
+	return float4((sum / 16.0).xxx, 1.0);
+}
+
 // Emitter program
 
 float4 EmitterVs(int3 pos: a_Pos, uint inst_id: SV_InstanceID): SV_Position {
@@ -0,0 +1,126 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Terrain mesh export to Wavefront OBJ and glTF 2.0.
+//
+// The terrain is Z-up, both formats are written Y-up: (x, y, z) becomes (x, z, -y).
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use TerrainVertex;
+
+fn y_up(v: [f32; 3]) -> [f32; 3] {
+    [v[0], v[2], -v[1]]
+}
+
+/// Writes the mesh as OBJ, with the vertex colors appended to the `v` lines.
+pub fn write_obj(path: &Path, vertices: &[TerrainVertex], indices: &[u32]) -> io::Result<()> {
+    let mut out = BufWriter::new(try!(File::create(path)));
+    try!(writeln!(out, "# terrain: {} vertices, {} triangles", vertices.len(), indices.len() / 3));
+    for v in vertices {
+        let p = y_up(v.pos);
+        try!(writeln!(out, "v {} {} {} {} {} {}", p[0], p[1], p[2], v.color[0], v.color[1], v.color[2]));
+    }
+    for v in vertices {
+        let n = y_up(v.normal);
+        try!(writeln!(out, "vn {} {} {}", n[0], n[1], n[2]));
+    }
+    for tri in indices.chunks(3) {
+        // OBJ indices are 1-based
+        let (a, b, c) = (tri[0] + 1, tri[1] + 1, tri[2] + 1);
+        try!(writeln!(out, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c));
+    }
+    out.flush()
+}
+
+/// Writes the mesh as a `.gltf` document next to a `.bin` buffer of the same name.
+pub fn write_gltf(path: &Path, vertices: &[TerrainVertex], indices: &[u32]) -> io::Result<()> {
+    let bin_path = path.with_extension("bin");
+    let bin_name = bin_path.file_name().unwrap().to_string_lossy().into_owned();
+
+    // non-interleaved layout: positions, normals, colors, indices
+    let attribute_size = vertices.len() * 3 * 4;
+    let index_size = indices.len() * 4;
+    let (mut min, mut max) = ([::std::f32::MAX; 3], [::std::f32::MIN; 3]);
+    {
+        let mut bin = BufWriter::new(try!(File::create(&bin_path)));
+        for v in vertices {
+            let p = y_up(v.pos);
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+                try!(bin.write_f32::<LittleEndian>(p[i]));
+            }
+        }
+        for v in vertices {
+            for &x in y_up(v.normal).iter() {
+                try!(bin.write_f32::<LittleEndian>(x));
+            }
+        }
+        for v in vertices {
+            for &x in v.color.iter() {
+                try!(bin.write_f32::<LittleEndian>(x));
+            }
+        }
+        for &i in indices {
+            try!(bin.write_u32::<LittleEndian>(i));
+        }
+        try!(bin.flush());
+    }
+
+    let mut out = BufWriter::new(try!(File::create(path)));
+    try!(write!(out, r#"{{
+  "asset": {{ "version": "2.0", "generator": "gfx_sciter_examples deferred terrain" }},
+  "scene": 0,
+  "scenes": [ {{ "nodes": [0] }} ],
+  "nodes": [ {{ "name": "terrain", "mesh": 0 }} ],
+  "meshes": [ {{
+    "name": "terrain",
+    "primitives": [ {{
+      "attributes": {{ "POSITION": 0, "NORMAL": 1, "COLOR_0": 2 }},
+      "indices": 3,
+      "mode": 4
+    }} ]
+  }} ],
+  "buffers": [ {{ "uri": "{uri}", "byteLength": {total} }} ],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": {attr}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {attr}, "byteLength": {attr}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {attr2}, "byteLength": {attr}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {attr3}, "byteLength": {index}, "target": 34963 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": {count}, "type": "VEC3",
+      "min": [{min0}, {min1}, {min2}], "max": [{max0}, {max1}, {max2}] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": {count}, "type": "VEC3" }},
+    {{ "bufferView": 2, "componentType": 5126, "count": {count}, "type": "VEC3" }},
+    {{ "bufferView": 3, "componentType": 5125, "count": {indices}, "type": "SCALAR" }}
+  ]
+}}
+"#,
+        uri = bin_name,
+        total = 3 * attribute_size + index_size,
+        attr = attribute_size,
+        attr2 = 2 * attribute_size,
+        attr3 = 3 * attribute_size,
+        index = index_size,
+        count = vertices.len(),
+        indices = indices.len(),
+        min0 = min[0], min1 = min[1], min2 = min[2],
+        max0 = max[0], max1 = max[1], max2 = max[2]));
+    out.flush()
+}
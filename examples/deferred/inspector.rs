@@ -0,0 +1,157 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// How the scene objects show up in the inspector panel.
+
+use gfx_sciter_examples::inspect::{Inspect, Property};
+
+use {Scene, TerrainNode, ConstantBlocks, TerrainParams, LightParams, CameraParams, MAX_TERRAIN_RESOLUTION, NUM_LIGHTS, LIGHT_RADIUS};
+
+fn float(value: f32, min: f32, max: f32) -> Property {
+    Property::Float { value: value, min: min, max: max }
+}
+
+fn int(value: i32, min: i32, max: i32) -> Property {
+    Property::Int { value: value, min: min, max: max }
+}
+
+impl Inspect for TerrainParams {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        vec![
+            ("seed", int(self.seed as i32, 0, ::std::i32::MAX)),
+            ("octaves", int(self.octaves as i32, 1, 8)),
+            ("frequency", float(self.frequency, 0.25, 4.0)),
+            ("amplitude", float(self.amplitude, 1.0, 50.0)),
+            ("scale", float(self.scale, 5.0, 100.0)),
+            ("resolution", int(self.resolution as i32, 64, MAX_TERRAIN_RESOLUTION as i32)),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        match (name, value) {
+            ("seed", &Property::Int { value, .. }) => self.seed = value as u32,
+            ("octaves", &Property::Int { value, .. }) => self.octaves = value as u32,
+            ("frequency", &Property::Float { value, .. }) => self.frequency = value,
+            ("amplitude", &Property::Float { value, .. }) => self.amplitude = value,
+            ("scale", &Property::Float { value, .. }) => self.scale = value,
+            ("resolution", &Property::Int { value, .. }) => self.resolution = value as usize,
+            _ => return false,
+        }
+        *self = self.clamped();
+        true
+    }
+}
+
+impl Inspect for LightParams {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        vec![
+            ("count", Property::Text(format!("{}", NUM_LIGHTS))),
+            ("radius", Property::Text(format!("{}", LIGHT_RADIUS))),
+            ("speed", float(self.speed, 0.0, 2.0)),
+            ("height", float(self.height, 0.0, 10.0)),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        match (name, value) {
+            ("speed", &Property::Float { value, .. }) => self.speed = value,
+            ("height", &Property::Float { value, .. }) => self.height = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Inspect for CameraParams {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        vec![
+            ("distance", float(self.distance, 5.0, 64.0)),
+            ("height", float(self.height, 0.0, 64.0)),
+            ("speed", float(self.speed, 0.0, 1.0)),
+            ("fov", float(self.fov, 20.0, 120.0)),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        match (name, value) {
+            ("distance", &Property::Float { value, .. }) => self.distance = value,
+            ("height", &Property::Float { value, .. }) => self.height = value,
+            ("speed", &Property::Float { value, .. }) => self.speed = value,
+            ("fov", &Property::Float { value, .. }) => self.fov = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// the terrain gets regenerated whenever one of its parameters changes
+impl Inspect for TerrainNode {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        self.params.properties()
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        let changed = self.params.set_property(name, value);
+        if changed {
+            self.regenerate.set(true);
+        }
+        changed
+    }
+}
+
+impl Inspect for ConstantBlocks {
+    fn children(&self) -> Vec<(&'static str, &Inspect)> {
+        vec![
+            ("TerrainLocals", &self.terrain as &Inspect),
+            ("CubeLocals", &self.light_volume as &Inspect),
+            ("LightLocals", &self.light as &Inspect),
+            ("SunLocals", &self.sun as &Inspect),
+            ("SsaoLocals", &self.ssao as &Inspect),
+            ("TonemapLocals", &self.tonemap as &Inspect),
+        ]
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Inspect> {
+        match name {
+            "TerrainLocals" => Some(&mut self.terrain),
+            "CubeLocals" => Some(&mut self.light_volume),
+            "LightLocals" => Some(&mut self.light),
+            "SunLocals" => Some(&mut self.sun),
+            "SsaoLocals" => Some(&mut self.ssao),
+            "TonemapLocals" => Some(&mut self.tonemap),
+            _ => None,
+        }
+    }
+}
+
+impl Inspect for Scene {
+    fn children(&self) -> Vec<(&'static str, &Inspect)> {
+        vec![
+            ("terrain", &self.terrain as &Inspect),
+            ("lights", &self.lights as &Inspect),
+            ("camera", &self.camera as &Inspect),
+            ("constants", &self.constants as &Inspect),
+        ]
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Inspect> {
+        match name {
+            "terrain" => Some(&mut self.terrain),
+            "lights" => Some(&mut self.lights),
+            "camera" => Some(&mut self.camera),
+            "constants" => Some(&mut self.constants),
+            _ => None,
+        }
+    }
+}
@@ -22,9 +22,20 @@
 // Second, the lights are rendered as cubes. each fragment reads from the geometry buffer,
 //         light is applied, and the result is written to the result buffer.
 //
-// The result buffer is then displayed.
+// The sun is a directional light: the terrain depth is rendered from the sun into a shadow map
+// before the passes above, and a fullscreen pass adds the shadowed sun light to the result buffer.
 //
-// Press 1-4 to show the immediate buffers. Press 0 to show the final result.
+// Between the two passes, ambient occlusion is estimated in screen space from the geometry buffer
+// and blurred. It darkens the diffuse term of the lights.
+//
+// The result buffer holds HDR values. It is tone mapped to the screen, optionally
+// adding a bloom computed from its bright parts at half resolution.
+//
+// The terrain is split into chunks, each drawn at a level of detail picked from its distance
+// to the camera. See `chunks.rs`.
+//
+// The strip of thumbnails shows the immediate buffers: position, normal, diffuse and ambient
+// occlusion. Clicking one shows it fullscreen, clicking it again shows the final result.
 
 extern crate cgmath;
 #[macro_use]
@@ -32,15 +43,23 @@ extern crate gfx;
 extern crate gfx_app;
 extern crate time;
 extern crate rand;
-extern crate genmesh;
 extern crate noise;
+extern crate rustc_serialize;
+extern crate byteorder;
 
 
 
 extern crate sciter;
+#[macro_use]
+extern crate gfx_sciter_examples;
+
+mod chunks;
+mod export;
+mod inspector;
+mod ramp;
 
 use std::any::Any;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::{Rc, Weak};
 
 type SciterHost = Rc<sciter::Host>;
@@ -48,6 +67,7 @@ type SciterHost = Rc<sciter::Host>;
 struct View {
     api: &'static sciter::ISciterAPI,
     hwnd: sciter::types::HWINDOW,
+    root: sciter::Element,
 }
 
 
@@ -56,37 +76,52 @@ use cgmath::{SquareMatrix, Matrix4, Point3, Vector3, EuclideanVector, deg};
 use cgmath::{Transform, AffineMatrix3};
 pub use gfx::format::Depth;
 pub use gfx_app::ColorFormat;
-use genmesh::{Vertices, Triangulate};
-use genmesh::generators::{SharedVertex, IndexedPolygon};
 use time::precise_time_s;
 
 use noise::{Seed, perlin2};
 
+use chunks::{ChunkedTerrain, CHUNK_CELLS, CHUNK_VERTICES};
+use ramp::{ColorRamp, ColorStop};
+use gfx_sciter_examples::{inspect, path_from_url, to_number};
+use gfx_sciter_examples::constants::Constants;
+use gfx_sciter_examples::reflect::{self, Reflection};
+use gfx_sciter_examples::graph::{Pass, RenderGraph};
+use gfx_sciter_examples::images::ImageRegistry;
+
 // Remember to also change the constants in the shaders
 const NUM_LIGHTS: usize = 250;
 const LIGHT_RADIUS: f32 = 3.0;
 const EMITTER_RADIUS: f32 = 0.2;
-const TERRAIN_SCALE: [f32; 3] = [25.0, 25.0, 25.0];
+// The terrain vertex buffer is allocated once for the finest grid the UI offers,
+// the resolution is always a multiple of the chunk size. Every chunk is generated at full
+// resolution on the CPU, which keeps this at 64 chunks, about 10 MB of vertices.
+const MAX_TERRAIN_RESOLUTION: usize = 512;
+// Upper bound of the SSAO kernel, remember to also change the constant in the shaders
+const MAX_SSAO_SAMPLES: usize = 64;
+// Shadow maps can't be created after startup, so every selectable size is created up front
+const SHADOW_MAP_SIZES: [gfx::tex::Size; 3] = [512, 1024, 2048];
 
 pub type GFormat = [f32; 4];
+// light accumulation, half floats are plenty for the 250 overlapping lights
+pub type HdrFormat = (gfx::format::R16_G16_B16_A16, gfx::format::Float);
 
 gfx_constant_struct!(LightInfo {
     pos: [f32; 4] = "pos",
 });
 
-gfx_vertex_struct!( TerrainVertex {
+reflect_vertex_struct!( TerrainVertex {
     pos: [f32; 3] = "a_Pos",
     normal: [f32; 3] = "a_Normal",
     color: [f32; 3] = "a_Color",
 });
 
-gfx_constant_struct!( TerrainLocals {
+inspect_constant_struct!( TerrainLocals {
     model: [[f32; 4]; 4] = "u_Model",
     view: [[f32; 4]; 4] = "u_View",
     proj: [[f32; 4]; 4] = "u_Proj",
 });
 
-gfx_pipeline!( terrain {
+reflect_pipeline!( terrain {
     vbuf: gfx::VertexBuffer<TerrainVertex> = (),
     locals: gfx::ConstantBuffer<TerrainLocals> = "TerrainLocals",
     //TODO: reconstruct the position from the depth instead of
@@ -141,12 +176,12 @@ pub static TERRAIN_FRAGMENT_SRC: &'static [u8] = b"
     }
 ";
 
-gfx_vertex_struct!( BlitVertex {
+reflect_vertex_struct!( BlitVertex {
     pos: [i8; 2] = "a_Pos",
     tex_coord: [i8; 2] = "a_TexCoord",
 });
 
-gfx_pipeline!( blit {
+reflect_pipeline!( blit {
     vbuf: gfx::VertexBuffer<BlitVertex> = (),
     tex: gfx::TextureSampler<[f32; 4]> = "t_BlitTex",
     out: gfx::RenderTarget<ColorFormat> = "Target0",
@@ -178,20 +213,366 @@ pub static BLIT_FRAGMENT_SRC: &'static [u8] = b"
     }
 ";
 
-gfx_vertex_struct!( CubeVertex {
+gfx_constant_struct!( SsaoSample {
+    offset: [f32; 4] = "offset",
+});
+
+inspect_constant_struct!( SsaoLocals {
+    view: [[f32; 4]; 4] = "u_View",
+    proj: [[f32; 4]; 4] = "u_Proj",
+    // x: radius, y: sample count, z: strength
+    params: [f32; 4] = "u_SsaoParams" => (0.0, 64.0),
+});
+
+reflect_pipeline!( ssao {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    locals: gfx::ConstantBuffer<SsaoLocals> = "SsaoLocals",
+    kernel: gfx::ConstantBuffer<SsaoSample> = "u_SsaoKernel",
+    tex_pos: gfx::TextureSampler<[f32; 4]> = "t_Position",
+    tex_normal: gfx::TextureSampler<[f32; 4]> = "t_Normal",
+    out: gfx::RenderTarget<GFormat> = "Target0",
+});
+
+pub static SSAO_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform SsaoLocals {
+        mat4 u_View;
+        mat4 u_Proj;
+        vec4 u_SsaoParams;
+    };
+
+    const int MAX_SAMPLES = 64;
+    layout(std140)
+    uniform u_SsaoKernel {
+        vec4 kernel[MAX_SAMPLES];
+    };
+
+    uniform sampler2D t_Position;
+    uniform sampler2D t_Normal;
+    out vec4 o_Color;
+
+    // random rotation of the kernel, tiled over 4x4 pixels like a small noise texture
+    float hash(vec2 p) {
+        return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+    }
+
+    void main() {
+        ivec2 itc = ivec2(gl_FragCoord.xy);
+        vec3 normal = texelFetch(t_Normal, itc, 0).xyz;
+        // nothing was rendered here
+        if (dot(normal, normal) < 0.5) {
+            o_Color = vec4(1.0);
+            return;
+        }
+
+        float radius = u_SsaoParams.x;
+        int count = int(u_SsaoParams.y);
+        vec3 pos = (u_View * vec4(texelFetch(t_Position, itc, 0).xyz, 1.0)).xyz;
+        vec3 n = normalize(mat3(u_View) * normal);
+
+        float angle = 6.2831853 * hash(mod(floor(gl_FragCoord.xy), 4.0));
+        vec3 rvec = vec3(cos(angle), sin(angle), 0.0);
+        vec3 t = normalize(rvec - n * dot(rvec, n));
+        mat3 tbn = mat3(t, cross(n, t), n);
+
+        ivec2 size = textureSize(t_Position, 0);
+        float occlusion = 0.0;
+        for (int i = 0; i < count; ++i) {
+            vec3 sample_pos = pos + radius * (tbn * kernel[i].xyz);
+            vec4 clip = u_Proj * vec4(sample_pos, 1.0);
+            ivec2 stc = ivec2((0.5 * clip.xy / clip.w + 0.5) * vec2(size));
+            if (any(lessThan(stc, ivec2(0))) || any(greaterThanEqual(stc, size)))
+                continue;
+            float depth = (u_View * vec4(texelFetch(t_Position, stc, 0).xyz, 1.0)).z;
+            float range = smoothstep(0.0, 1.0, radius / abs(pos.z - depth));
+            occlusion += (depth >= sample_pos.z + 0.05 ? 1.0 : 0.0) * range;
+        }
+
+        float ao = 1.0 - u_SsaoParams.z * occlusion / max(float(count), 1.0);
+        o_Color = vec4(vec3(clamp(ao, 0.0, 1.0)), 1.0);
+    }
+";
+
+reflect_pipeline!( ssao_blur {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    tex: gfx::TextureSampler<[f32; 4]> = "t_Occlusion",
+    out: gfx::RenderTarget<GFormat> = "Target0",
+});
+
+pub static SSAO_BLUR_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    uniform sampler2D t_Occlusion;
+    out vec4 o_Color;
+
+    // 4x4 box blur, matching the period of the kernel rotation noise
+    void main() {
+        ivec2 itc = ivec2(gl_FragCoord.xy);
+        ivec2 size = textureSize(t_Occlusion, 0);
+        float sum = 0.0;
+        for (int y = -2; y < 2; ++y) {
+            for (int x = -2; x < 2; ++x) {
+                ivec2 tc = clamp(itc + ivec2(x, y), ivec2(0), size - 1);
+                sum += texelFetch(t_Occlusion, tc, 0).r;
+            }
+        }
+        o_Color = vec4(vec3(sum / 16.0), 1.0);
+    }
+";
+
+gfx_constant_struct!( BloomLocals {
+    // x: brightness threshold, y: pass (0 - bright pass, 1 - horizontal blur, 2 - vertical blur),
+    // zw: texel size of the target
+    params: [f32; 4] = "u_BloomParams",
+});
+
+reflect_pipeline!( bloom {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    locals: gfx::ConstantBuffer<BloomLocals> = "BloomLocals",
+    tex: gfx::TextureSampler<[f32; 4]> = "t_Source",
+    out: gfx::RenderTarget<HdrFormat> = "Target0",
+});
+
+pub static BLOOM_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform BloomLocals {
+        vec4 u_BloomParams;
+    };
+    uniform sampler2D t_Source;
+    in vec2 v_TexCoord;
+    out vec4 o_Color;
+
+    const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    void main() {
+        if (u_BloomParams.y < 0.5) {
+            // the linear sampler averages 2x2 texels of the source
+            vec3 color = texture(t_Source, v_TexCoord).rgb;
+            float lum = dot(color, vec3(0.2126, 0.7152, 0.0722));
+            o_Color = vec4(color * max(0.0, lum - u_BloomParams.x) / max(lum, 0.0001), 1.0);
+        } else {
+            vec2 dir = u_BloomParams.y < 1.5 ? vec2(u_BloomParams.z, 0.0) : vec2(0.0, u_BloomParams.w);
+            vec3 sum = weights[0] * texture(t_Source, v_TexCoord).rgb;
+            for (int i = 1; i < 5; ++i) {
+                sum += weights[i] * texture(t_Source, v_TexCoord + i * dir).rgb;
+                sum += weights[i] * texture(t_Source, v_TexCoord - i * dir).rgb;
+            }
+            o_Color = vec4(sum, 1.0);
+        }
+    }
+";
+
+inspect_constant_struct!( TonemapLocals {
+    // x: exposure, y: operator (0 - clamp, 1 - Reinhard, 2 - ACES), z: bloom strength
+    params: [f32; 4] = "u_Tonemap" => (0.0, 4.0),
+});
+
+reflect_pipeline!( tonemap {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    locals: gfx::ConstantBuffer<TonemapLocals> = "TonemapLocals",
+    tex_hdr: gfx::TextureSampler<[f32; 4]> = "t_Hdr",
+    tex_bloom: gfx::TextureSampler<[f32; 4]> = "t_Bloom",
+    out: gfx::RenderTarget<ColorFormat> = "Target0",
+});
+
+pub static TONEMAP_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform TonemapLocals {
+        vec4 u_Tonemap;
+    };
+    uniform sampler2D t_Hdr;
+    uniform sampler2D t_Bloom;
+    in vec2 v_TexCoord;
+    out vec4 o_Color;
+
+    // Krzysztof Narkowicz's fit of the ACES filmic curve
+    vec3 aces(vec3 x) {
+        return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    }
+
+    void main() {
+        vec3 color = texture(t_Hdr, v_TexCoord).rgb + u_Tonemap.z * texture(t_Bloom, v_TexCoord).rgb;
+        color *= u_Tonemap.x;
+        if (u_Tonemap.y > 1.5) {
+            color = aces(color);
+        } else if (u_Tonemap.y > 0.5) {
+            color = color / (vec3(1.0) + color);
+        } else {
+            color = clamp(color, 0.0, 1.0);
+        }
+        o_Color = vec4(color, 1.0);
+    }
+";
+
+gfx_constant_struct!( ThumbnailLocals {
+    // the box of the image element in normalized device coordinates: left, bottom, width, height
+    rect: [f32; 4] = "u_Rect",
+});
+
+reflect_pipeline!( thumbnail {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    locals: gfx::ConstantBuffer<ThumbnailLocals> = "ThumbnailLocals",
+    tex: gfx::TextureSampler<[f32; 4]> = "t_Source",
+    out: gfx::RenderTarget<ColorFormat> = "Target0",
+});
+
+pub static THUMBNAIL_VERTEX_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform ThumbnailLocals {
+        vec4 u_Rect;
+    };
+    in ivec2 a_Pos;
+    in ivec2 a_TexCoord;
+    out vec2 v_TexCoord;
+
+    void main() {
+        // the fullscreen triangle, scaled down to cover the box
+        v_TexCoord = a_TexCoord;
+        gl_Position = vec4(u_Rect.xy + v_TexCoord * u_Rect.zw, 0.0, 1.0);
+    }
+";
+
+pub static THUMBNAIL_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    uniform sampler2D t_Source;
+    in vec2 v_TexCoord;
+    out vec4 o_Color;
+
+    void main() {
+        if (any(lessThan(v_TexCoord, vec2(0.0))) || any(greaterThan(v_TexCoord, vec2(1.0)))) {
+            discard;
+        }
+        o_Color = vec4(texture(t_Source, v_TexCoord).rgb, 1.0);
+    }
+";
+
+gfx_constant_struct!( ShadowLocals {
+    transform: [[f32; 4]; 4] = "u_LightTransform",
+});
+
+reflect_pipeline!( shadow {
+    vbuf: gfx::VertexBuffer<TerrainVertex> = (),
+    locals: gfx::ConstantBuffer<ShadowLocals> = "ShadowLocals",
+    out_depth: gfx::DepthTarget<Depth> =
+        gfx::preset::depth::LESS_EQUAL_WRITE,
+});
+
+pub static SHADOW_VERTEX_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform ShadowLocals {
+        mat4 u_LightTransform;
+    };
+    in vec3 a_Pos;
+
+    void main() {
+        gl_Position = u_LightTransform * vec4(a_Pos, 1.0);
+    }
+";
+
+pub static SHADOW_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    void main() {
+    }
+";
+
+inspect_constant_struct!( SunLocals {
+    light_transform: [[f32; 4]; 4] = "u_LightTransform",
+    // xyz: direction towards the sun
+    direction: [f32; 4] = "u_SunDirection",
+    // rgb: sun color, w: ambient term
+    color: [f32; 4] = "u_SunColor" => (0.0, 4.0),
+    // x: depth bias, y: shadow map texel size
+    shadow_params: [f32; 4] = "u_ShadowParams" => (0.0, 0.05),
+});
+
+reflect_pipeline!( sun {
+    vbuf: gfx::VertexBuffer<BlitVertex> = (),
+    locals: gfx::ConstantBuffer<SunLocals> = "SunLocals",
+    tex_pos: gfx::TextureSampler<[f32; 4]> = "t_Position",
+    tex_normal: gfx::TextureSampler<[f32; 4]> = "t_Normal",
+    tex_diffuse: gfx::TextureSampler<[f32; 4]> = "t_Diffuse",
+    tex_shadow: gfx::TextureSampler<f32> = "t_Shadow",
+    tex_occlusion: gfx::TextureSampler<[f32; 4]> = "t_Occlusion",
+    out_color: gfx::BlendTarget<HdrFormat> =
+        ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::ADD),
+});
+
+pub static SUN_FRAGMENT_SRC: &'static [u8] = b"
+    #version 150 core
+
+    layout(std140)
+    uniform SunLocals {
+        mat4 u_LightTransform;
+        vec4 u_SunDirection;
+        vec4 u_SunColor;
+        vec4 u_ShadowParams;
+    };
+    uniform sampler2D t_Position;
+    uniform sampler2D t_Normal;
+    uniform sampler2D t_Diffuse;
+    uniform sampler2D t_Shadow;
+    uniform sampler2D t_Occlusion;
+    out vec4 o_Color;
+
+    float shadow_factor(vec3 pos) {
+        vec4 light_pos = u_LightTransform * vec4(pos, 1.0);
+        vec3 coord = 0.5 * light_pos.xyz / light_pos.w + 0.5;
+        // 3x3 percentage closer filtering
+        float lit = 0.0;
+        for (int y = -1; y <= 1; ++y) {
+            for (int x = -1; x <= 1; ++x) {
+                float depth = texture(t_Shadow, coord.xy + vec2(x, y) * u_ShadowParams.y).r;
+                lit += coord.z - u_ShadowParams.x <= depth ? 1.0 : 0.0;
+            }
+        }
+        return lit / 9.0;
+    }
+
+    void main() {
+        ivec2 itc = ivec2(gl_FragCoord.xy);
+        vec3 pos     = texelFetch(t_Position, itc, 0).xyz;
+        vec3 normal  = texelFetch(t_Normal,   itc, 0).xyz;
+        vec3 diffuse = texelFetch(t_Diffuse,  itc, 0).xyz;
+
+        // nothing was rendered here
+        if (dot(normal, normal) < 0.5)
+            discard;
+
+        vec3 n = normalize(normal);
+        float d = max(0.0, dot(n, u_SunDirection.xyz));
+        float lit = d > 0.0 ? shadow_factor(pos) : 0.0;
+        float ao = texelFetch(t_Occlusion, itc, 0).r;
+
+        o_Color = vec4((u_SunColor.w + lit * d * u_SunColor.rgb) * ao * diffuse, 1.0);
+    }
+";
+
+reflect_vertex_struct!( CubeVertex {
     pos: [i8; 4] = "a_Pos",
 });
 
-gfx_constant_struct!( CubeLocals {
+inspect_constant_struct!( CubeLocals {
     transform: [[f32; 4]; 4] = "u_Transform",
-    radius: f32 = "u_Radius",
+    radius: f32 = "u_Radius" => (0.0, 10.0),
 });
 
-gfx_constant_struct!( LightLocals {
-    cam_pos_and_radius: [f32; 4] = "u_CameraPosAndRadius",
+inspect_constant_struct!( LightLocals {
+    cam_pos_and_radius: [f32; 4] = "u_CameraPosAndRadius" => (-64.0, 64.0),
 });
 
-gfx_pipeline!( light {
+reflect_pipeline!( light {
     vbuf: gfx::VertexBuffer<CubeVertex> = (),
     locals_vs: gfx::ConstantBuffer<CubeLocals> = "CubeLocals",
     locals_ps: gfx::ConstantBuffer<LightLocals> = "LightLocals",
@@ -199,7 +580,8 @@ gfx_pipeline!( light {
     tex_pos: gfx::TextureSampler<[f32; 4]> = "t_Position",
     tex_normal: gfx::TextureSampler<[f32; 4]> = "t_Normal",
     tex_diffuse: gfx::TextureSampler<[f32; 4]> = "t_Diffuse",
-    out_color: gfx::BlendTarget<GFormat> =
+    tex_occlusion: gfx::TextureSampler<[f32; 4]> = "t_Occlusion",
+    out_color: gfx::BlendTarget<HdrFormat> =
         ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::ADD),
     out_depth: gfx::DepthTarget<Depth> =
         gfx::preset::depth::LESS_EQUAL_TEST,
@@ -239,6 +621,7 @@ pub static LIGHT_FRAGMENT_SRC: &'static [u8] = b"
     uniform sampler2D t_Position;
     uniform sampler2D t_Normal;
     uniform sampler2D t_Diffuse;
+    uniform sampler2D t_Occlusion;
     in vec3 v_LightPos;
     out vec4 o_Color;
 
@@ -259,17 +642,19 @@ pub static LIGHT_FRAGMENT_SRC: &'static [u8] = b"
         float dist_sq = dot(light - pos, light - pos);
         float scale = max(0.0, 1.0 - dist_sq * u_CameraPosAndRadius.w);
 
-        vec3 res_color = d * diffuse + vec3(s);
+        float ao = texelFetch(t_Occlusion, itc, 0).r;
+
+        vec3 res_color = d * ao * diffuse + vec3(s);
 
         o_Color = vec4(scale*res_color, 1.0);
     }
 ";
 
-gfx_pipeline!( emitter {
+reflect_pipeline!( emitter {
     vbuf: gfx::VertexBuffer<CubeVertex> = (),
     locals: gfx::ConstantBuffer<CubeLocals> = "CubeLocals",
     light_pos_buf: gfx::ConstantBuffer<LightInfo> = "u_LightPosBlock",
-    out_color: gfx::BlendTarget<GFormat> =
+    out_color: gfx::BlendTarget<HdrFormat> =
         ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::ADD),
     out_depth: gfx::DepthTarget<Depth> =
         gfx::preset::depth::LESS_EQUAL_TEST,
@@ -307,16 +692,74 @@ pub static EMITTER_FRAGMENT_SRC: &'static [u8] = b"
     }
 ";
 
-fn calculate_normal(seed: &Seed, x: f32, y: f32)-> [f32; 3] {
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TerrainParams {
+    seed: u32,
+    octaves: u32,
+    frequency: f32,
+    amplitude: f32,
+    scale: f32,
+    resolution: usize,
+}
+
+impl TerrainParams {
+    fn new(seed: u32) -> TerrainParams {
+        TerrainParams {
+            seed: seed,
+            octaves: 1,
+            frequency: 1.0,
+            amplitude: 25.0,
+            scale: 25.0,
+            resolution: 256,
+        }
+    }
+
+    // keep whatever comes from the UI within the preallocated buffers,
+    // and the seed within the integers the script has
+    fn clamped(self) -> TerrainParams {
+        TerrainParams {
+            seed: self.seed.min(::std::i32::MAX as u32),
+            octaves: self.octaves.max(1).min(8),
+            resolution: (self.resolution / CHUNK_CELLS).max(1).min(MAX_TERRAIN_RESOLUTION / CHUNK_CELLS) * CHUNK_CELLS,
+            .. self
+        }
+    }
+
+    // world space height at the plane coordinates (x, y) in [-1, 1]
+    fn height(&self, seed: &Seed, x: f32, y: f32) -> f32 {
+        self.amplitude * fractal_noise(seed, self, x, y)
+    }
+
+    // maps a world space height into [0, 1] for the color ramp
+    fn normalized_height(&self, height: f32) -> f32 {
+        let t = 0.5 + 0.5 * height / self.amplitude;
+        t.max(0.0).min(1.0)
+    }
+}
+
+fn fractal_noise(seed: &Seed, params: &TerrainParams, x: f32, y: f32) -> f32 {
+    let mut sum = 0.0;
+    let mut frequency = params.frequency;
+    let mut weight = 1.0;
+    for _ in 0..params.octaves {
+        sum += weight * perlin2(seed, &[frequency * x, frequency * y]);
+        frequency *= 2.0;
+        weight *= 0.5;
+    }
+    sum
+}
+
+fn calculate_normal(seed: &Seed, params: &TerrainParams, x: f32, y: f32)-> [f32; 3] {
     // determine sample points
     let s_x0 = x - 0.001;
     let s_x1 = x + 0.001;
     let s_y0 = y - 0.001;
     let s_y1 = y + 0.001;
 
-    // calculate gradient in point
-    let dzdx = (perlin2(seed, &[s_x1, y]) - perlin2(seed, &[s_x0, y]))/(s_x1 - s_x0);
-    let dzdy = (perlin2(seed, &[x, s_y1]) - perlin2(seed, &[x, s_y0]))/(s_y1 - s_y0);
+    // calculate gradient in point, scaled from noise into world space
+    let slope = params.amplitude / params.scale;
+    let dzdx = slope * (fractal_noise(seed, params, s_x1, y) - fractal_noise(seed, params, s_x0, y))/(s_x1 - s_x0);
+    let dzdy = slope * (fractal_noise(seed, params, x, s_y1) - fractal_noise(seed, params, x, s_y0))/(s_y1 - s_y0);
 
     // cross gradient vectors to get normal
     let normal = Vector3::new(1.0, 0.0, dzdx).cross(Vector3::new(0.0, 1.0, dzdy)).normalize();
@@ -324,18 +767,254 @@ fn calculate_normal(seed: &Seed, x: f32, y: f32)-> [f32; 3] {
     return normal.into();
 }
 
-fn calculate_color(height: f32) -> [f32; 3] {
-    if height > 8.0 {
-        [0.9, 0.9, 0.9] // white
-    } else if height > 0.0 {
-        [0.7, 0.7, 0.7] // greay
-    } else if height > -5.0 {
-        [0.2, 0.7, 0.2] // green
-    } else {
-        [0.2, 0.2, 0.7] // blue
+// Vertices of all the chunks, chunk after chunk in rows, each one at full resolution.
+// Vertices along the chunk borders are duplicated.
+fn generate_terrain(params: &TerrainParams, ramp: &ColorRamp) -> Vec<TerrainVertex> {
+    let seed = Seed::new(params.seed);
+    let chunks = params.resolution / CHUNK_CELLS;
+    let cell = 2.0 / params.resolution as f32;
+    let mut vertex_data = Vec::with_capacity(chunks * chunks * CHUNK_VERTICES);
+    for cy in 0..chunks {
+        for cx in 0..chunks {
+            for j in 0..CHUNK_CELLS + 1 {
+                for i in 0..CHUNK_CELLS + 1 {
+                    let x = -1.0 + (cx * CHUNK_CELLS + i) as f32 * cell;
+                    let y = -1.0 + (cy * CHUNK_CELLS + j) as f32 * cell;
+                    let h = params.height(&seed, x, y);
+                    vertex_data.push(TerrainVertex {
+                        pos: [params.scale * x, params.scale * y, h],
+                        normal: calculate_normal(&seed, params, x, y),
+                        color: ramp.sample(params.normalized_height(h)),
+                    });
+                }
+            }
+        }
+    }
+    vertex_data
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SunParams {
+    // degrees, counted from the +X axis
+    azimuth: f32,
+    // degrees above the horizon
+    elevation: f32,
+    intensity: f32,
+    ambient: f32,
+    bias: f32,
+    // index into SHADOW_MAP_SIZES
+    shadow_map: usize,
+}
+
+impl Default for SunParams {
+    fn default() -> SunParams {
+        SunParams {
+            azimuth: 45.0,
+            elevation: 40.0,
+            intensity: 0.6,
+            ambient: 0.05,
+            bias: 0.002,
+            shadow_map: 1,
+        }
+    }
+}
+
+impl SunParams {
+    // unit vector pointing towards the sun
+    fn direction(&self) -> Vector3<f32> {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        Vector3::new(elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(), elevation.sin())
+    }
+
+    // view-projection of the sun, fitted around the bounding sphere of the terrain
+    fn light_transform(&self, params: &TerrainParams) -> Matrix4<f32> {
+        let radius = (2.0 * params.scale * params.scale + params.amplitude * params.amplitude).sqrt();
+        let eye = self.direction() * (2.0 * radius);
+        let view: AffineMatrix3<f32> = Transform::look_at(
+            Point3::new(eye.x, eye.y, eye.z),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_z(),
+        );
+        let proj = cgmath::ortho(-radius, radius, -radius, radius, radius, 3.0 * radius);
+        // remap depth from [-1, 1] to [0, 1], so that no backend clips half of the range
+        let depth_remap = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.0, 0.0, 0.5, 1.0);
+        depth_remap * proj * view.mat
+    }
+}
+
+// the camera slowly circles the center of the terrain
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CameraParams {
+    distance: f32,
+    height: f32,
+    // radians per second
+    speed: f32,
+    // vertical field of view in degrees
+    fov: f32,
+}
+
+impl Default for CameraParams {
+    fn default() -> CameraParams {
+        CameraParams {
+            distance: 32.0,
+            height: 16.0,
+            speed: 0.05,
+            fov: 60.0,
+        }
     }
 }
 
+// the point lights circling over the terrain
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct LightParams {
+    // radians per second
+    speed: f32,
+    // above the ground
+    height: f32,
+}
+
+impl Default for LightParams {
+    fn default() -> LightParams {
+        LightParams {
+            speed: 0.2,
+            height: 0.5,
+        }
+    }
+}
+
+// the objects shown in the inspector panel, see inspector.rs
+struct TerrainNode {
+    params: Rc<Cell<TerrainParams>>,
+    regenerate: Rc<Cell<bool>>,
+}
+
+// the constant buffers, as uploaded by the last frame plus the edits made in the inspector
+#[derive(Clone)]
+struct ConstantBlocks {
+    terrain: Rc<RefCell<Constants<TerrainLocals>>>,
+    light_volume: Rc<RefCell<Constants<CubeLocals>>>,
+    light: Rc<RefCell<Constants<LightLocals>>>,
+    sun: Rc<RefCell<Constants<SunLocals>>>,
+    ssao: Rc<RefCell<Constants<SsaoLocals>>>,
+    tonemap: Rc<RefCell<Constants<TonemapLocals>>>,
+}
+
+impl ConstantBlocks {
+    fn new() -> ConstantBlocks {
+        ConstantBlocks {
+            terrain: Rc::new(RefCell::new(Constants::new())),
+            light_volume: Rc::new(RefCell::new(Constants::new())),
+            light: Rc::new(RefCell::new(Constants::new())),
+            sun: Rc::new(RefCell::new(Constants::new())),
+            ssao: Rc::new(RefCell::new(Constants::new())),
+            tonemap: Rc::new(RefCell::new(Constants::new())),
+        }
+    }
+}
+
+struct Scene {
+    terrain: TerrainNode,
+    lights: Rc<Cell<LightParams>>,
+    camera: Rc<Cell<CameraParams>>,
+    constants: ConstantBlocks,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+const TONE_MAPPINGS: [(&'static str, ToneMapping); 3] = [
+    ("Clamp", ToneMapping::Clamp),
+    ("Reinhard", ToneMapping::Reinhard),
+    ("ACES", ToneMapping::Aces),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HdrParams {
+    exposure: f32,
+    tone_mapping: ToneMapping,
+    bloom: bool,
+    bloom_threshold: f32,
+    bloom_strength: f32,
+    // number of horizontal + vertical blur rounds
+    bloom_passes: u32,
+}
+
+impl Default for HdrParams {
+    fn default() -> HdrParams {
+        HdrParams {
+            exposure: 1.0,
+            tone_mapping: ToneMapping::Aces,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_strength: 0.5,
+            bloom_passes: 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SsaoParams {
+    radius: f32,
+    samples: u32,
+    strength: f32,
+}
+
+impl Default for SsaoParams {
+    fn default() -> SsaoParams {
+        SsaoParams {
+            radius: 1.5,
+            samples: 16,
+            strength: 1.0,
+        }
+    }
+}
+
+// samples in the +Z hemisphere, denser close to the origin
+fn generate_ssao_kernel() -> Vec<SsaoSample> {
+    let mut rng = rand::thread_rng();
+    (0..MAX_SSAO_SAMPLES).map(|i| {
+        let dir = Vector3::new(2.0 * rng.gen::<f32>() - 1.0,
+                               2.0 * rng.gen::<f32>() - 1.0,
+                               rng.gen::<f32>()).normalize();
+        let t = i as f32 / MAX_SSAO_SAMPLES as f32;
+        let scale = rng.gen::<f32>() * (0.1 + 0.9 * t * t);
+        SsaoSample { offset: [scale * dir.x, scale * dir.y, scale * dir.z, 0.0] }
+    }).collect()
+}
+
+// CPU copy of the terrain vertices with the full resolution indices of every chunk, for exporting
+#[derive(Default)]
+struct TerrainMesh {
+    vertices: Vec<TerrainVertex>,
+    indices: Vec<u32>,
+}
+
+impl TerrainMesh {
+    fn export(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let ext = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        match ext.as_ref().map(|e| &e[..]) {
+            Some("obj") => export::write_obj(path, &self.vertices, &self.indices),
+            Some("gltf") => export::write_gltf(path, &self.vertices, &self.indices),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                                         "expected an .obj or .gltf file name")),
+        }
+    }
+}
+
+struct ShadowMap<R: gfx::Resources> {
+    resource: gfx::handle::ShaderResourceView<R, f32>,
+    target: gfx::handle::DepthStencilView<R, Depth>,
+    size: gfx::tex::Size,
+}
+
 struct ViewPair<R: gfx::Resources, T: gfx::format::Formatted> {
     resource: gfx::handle::ShaderResourceView<R, T::View>,
     target: gfx::handle::RenderTargetView<R, T>,
@@ -381,17 +1060,70 @@ fn create_g_buffer<R: gfx::Resources, F: gfx::Factory<R>>(
 }
 
 
+// The passes of a frame. The lighting passes accumulate into the HDR buffer, `blit` shows
+// one of the intermediate buffers instead when picked in the UI.
+fn render_graph() -> RenderGraph {
+    const GBUFFER: [&'static str; 3] = ["position", "normal", "diffuse"];
+    RenderGraph::new(vec![
+        Pass::new("shadow").clears(&["shadow map"]),
+        Pass::new("terrain").clears(&GBUFFER).clears(&["depth"]),
+        Pass::new("ssao").reads(&GBUFFER[..2]).writes(&["occlusion"]),
+        Pass::new("ssao_blur").reads(&["occlusion"]).writes(&["blurred occlusion"]),
+        Pass::new("sun").reads(&GBUFFER).reads(&["blurred occlusion", "shadow map"]).clears(&["hdr"]),
+        Pass::new("light").reads(&GBUFFER).reads(&["blurred occlusion", "depth"]).writes(&["hdr"]),
+        Pass::new("emitter").reads(&["depth"]).writes(&["hdr"]),
+        Pass::new("bloom").reads(&["hdr"]).writes(&["bloom"]),
+        Pass::new("tonemap").reads(&["hdr", "bloom"]).writes(&["frame"]),
+        Pass::new("blit").reads(&GBUFFER).reads(&["blurred occlusion"]).writes(&["frame"]),
+        Pass::new("thumbnails").reads(&GBUFFER).reads(&["blurred occlusion"]).writes(&["frame"]),
+    ]).unwrap()
+}
+
+// whether a pass runs, depending on the buffer shown and the bloom setting
+fn pass_enabled(name: &str, debug_buffer: bool, bloom: bool) -> bool {
+    match name {
+        "blit" => debug_buffer,
+        "bloom" => !debug_buffer && bloom,
+        "sun" | "light" | "emitter" | "tonemap" => !debug_buffer,
+        _ => true,
+    }
+}
+
 struct App<R: gfx::Resources> {
     terrain: terrain::Bundle<R>,
     blit: blit::Bundle<R>,
+    thumbnail: thumbnail::Bundle<R>,
     light: light::Bundle<R>,
     emitter: emitter::Bundle<R>,
-    intermediate: ViewPair<R, GFormat>,
+    shadow: shadow::Bundle<R>,
+    sun: sun::Bundle<R>,
+    shadow_maps: Vec<ShadowMap<R>>,
+    sun_params: Rc<Cell<SunParams>>,
+    camera_params: Rc<Cell<CameraParams>>,
+    light_params: Rc<Cell<LightParams>>,
+    bloom: bloom::Bundle<R>,
+    tonemap: tonemap::Bundle<R>,
+    bloom_targets: [ViewPair<R, HdrFormat>; 2],
+    hdr_params: Rc<Cell<HdrParams>>,
+    ssao: ssao::Bundle<R>,
+    ssao_blur: ssao_blur::Bundle<R>,
+    ssao_params: Rc<Cell<SsaoParams>>,
+    ssao_kernel_dirty: bool,
+    chunks: ChunkedTerrain<R>,
+    intermediate: ViewPair<R, HdrFormat>,
     light_pos_vec: Vec<LightInfo>,
     seed: Seed,
+    terrain_params: Rc<Cell<TerrainParams>>,
+    color_ramp: Rc<RefCell<ColorRamp>>,
+    regenerate: Rc<Cell<bool>>,
+    terrain_mesh: Rc<RefCell<TerrainMesh>>,
+    constants: ConstantBlocks,
     // debug_buf: Option<gfx::handle::ShaderResourceView<R, [f32; 4]>>,
     debug_buffers: Vec<Option<gfx::handle::ShaderResourceView<R, [f32; 4]>>>,
     buf_num: Rc<Cell<u8>>,
+    images: ImageRegistry<R>,
+    reflections: Vec<Reflection>,
+    graph: Rc<RenderGraph>,
     view: Option<View>,
 }
 
@@ -399,6 +1131,9 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
     fn new<F: gfx::Factory<R>>(mut factory: F, init: gfx_app::Init<R>) -> Self {
         use gfx::traits::FactoryExt;
 
+        // the reflection of every pipeline, for the debug panel
+        let mut reflections = Vec::new();
+
         let (width, height, _, _) = init.color.get_dimensions();
         let (gpos, gnormal, gdiffuse, _depth_resource, depth_target) =
             create_g_buffer(width, height, &mut factory);
@@ -407,9 +1142,16 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
             ViewPair{ resource: srv, target: rtv }
         };
 
-        let seed = {
-            let rand_seed = rand::thread_rng().gen();
-            Seed::new(rand_seed)
+        let params = TerrainParams::new(rand::thread_rng().gen_range(0, ::std::i32::MAX as u32));
+
+        // raw and blurred ambient occlusion
+        let ao = {
+            let (_ , srv, rtv) = factory.create_render_target(width, height).unwrap();
+            ViewPair{ resource: srv, target: rtv }
+        };
+        let ao_blurred = {
+            let (_ , srv, rtv) = factory.create_render_target(width, height).unwrap();
+            ViewPair{ resource: srv, target: rtv }
         };
 
         let sampler = factory.create_sampler(
@@ -417,26 +1159,21 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                                        gfx::tex::WrapMode::Clamp)
         );
 
+        let chunks = ChunkedTerrain::new(&mut factory);
+
         let terrain = {
-            let plane = genmesh::generators::Plane::subdivide(256, 256);
-            let vertex_data: Vec<TerrainVertex> = plane.shared_vertex_iter()
-                .map(|(x, y)| {
-                    let h = TERRAIN_SCALE[2] * perlin2(&seed, &[x, y]);
-                    TerrainVertex {
-                        pos: [TERRAIN_SCALE[0] * x, TERRAIN_SCALE[1] * y, h],
-                        normal: calculate_normal(&seed, x, y),
-                        color: calculate_color(h),
-                    }
-                })
-                .collect();
-
-            let index_data: Vec<u32> = plane.indexed_polygon_iter()
-                .triangulate()
-                .vertices()
-                .map(|i| i as u32)
-                .collect();
-
-            let (vbuf, slice) = factory.create_vertex_buffer_indexed(&vertex_data, &index_data[..]);
+            // The terrain can be regenerated at runtime, so its vertex buffer is dynamic
+            // and sized for the finest grid. It is filled on the first frame.
+            let max_chunks = (MAX_TERRAIN_RESOLUTION / CHUNK_CELLS) * (MAX_TERRAIN_RESOLUTION / CHUNK_CELLS);
+            let vbuf = factory.create_buffer_dynamic(max_chunks * CHUNK_VERTICES, gfx::BufferRole::Vertex, gfx::Bind::empty()).unwrap();
+            // unused, the chunks are drawn with their own slices
+            let slice = gfx::Slice {
+                start: 0,
+                end: 0,
+                base_vertex: 0,
+                instances: None,
+                buffer: gfx::IndexBuffer::Auto,
+            };
 
             let vs = gfx_app::shade::Source {
                 glsl_150: TERRAIN_VERTEX_SRC,
@@ -449,11 +1186,12 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 .. gfx_app::shade::Source::empty()
             };
 
-            let pso = factory.create_pipeline_simple(
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "terrain",
                 vs.select(init.backend).unwrap(),
                 ps.select(init.backend).unwrap(),
                 gfx::state::CullFace::Back, terrain::new()
                 ).unwrap();
+            reflections.push(reflection);
 
             let data = terrain::Data {
                 vbuf: vbuf,
@@ -467,6 +1205,39 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
             terrain::bundle(slice, pso, data)
         };
 
+        let shadow_maps: Vec<_> = SHADOW_MAP_SIZES.iter().map(|&size| {
+            let (_, srv, dsv) = factory.create_depth_stencil(size, size).unwrap();
+            ShadowMap { resource: srv, target: dsv, size: size }
+        }).collect();
+
+        let shadow = {
+            let vs = gfx_app::shade::Source {
+                glsl_150: SHADOW_VERTEX_SRC,
+                hlsl_40:  include_bytes!("data/shadow_vs.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+            let ps = gfx_app::shade::Source {
+                glsl_150: SHADOW_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/shadow_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "shadow",
+                vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, shadow::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = shadow::Data {
+                vbuf: terrain.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                out_depth: shadow_maps[0].target.clone(),
+            };
+
+            shadow::bundle(terrain.slice.clone(), pso, data)
+        };
+
         let blit = {
             let vertex_data = [
                 BlitVertex { pos: [-3, -1], tex_coord: [-1, 0] },
@@ -487,21 +1258,207 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 .. gfx_app::shade::Source::empty()
             };
 
-            let pso = factory.create_pipeline_simple(
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "blit",
                 vs.select(init.backend).unwrap(),
                 ps.select(init.backend).unwrap(),
                 gfx::state::CullFace::Nothing, blit::new()
                 ).unwrap();
+            reflections.push(reflection);
 
             let data = blit::Data {
                 vbuf: vbuf,
                 tex: (gpos.resource.clone(), sampler.clone()),
-                out: init.color,
+                out: init.color.clone(),
             };
 
             blit::bundle(slice, pso, data)
         };
 
+        // fullscreen passes reading the buffers with texel fetches
+        let vs_fullscreen = gfx_app::shade::Source {
+            glsl_150: BLIT_VERTEX_SRC,
+            hlsl_40:  include_bytes!("data/blit_vs.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+
+        let sun = {
+            let ps = gfx_app::shade::Source {
+                glsl_150: SUN_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/sun_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "sun",
+                vs_fullscreen.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, sun::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = sun::Data {
+                vbuf: blit.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                tex_pos: (gpos.resource.clone(), sampler.clone()),
+                tex_normal: (gnormal.resource.clone(), sampler.clone()),
+                tex_diffuse: (gdiffuse.resource.clone(), sampler.clone()),
+                tex_shadow: (shadow_maps[0].resource.clone(), sampler.clone()),
+                tex_occlusion: (ao_blurred.resource.clone(), sampler.clone()),
+                out_color: res.target.clone(),
+            };
+
+            sun::bundle(blit.slice.clone(), pso, data)
+        };
+
+        let ssao = {
+            let ps = gfx_app::shade::Source {
+                glsl_150: SSAO_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/ssao_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "ssao",
+                vs_fullscreen.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, ssao::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let kernel = factory.create_constant_buffer(MAX_SSAO_SAMPLES);
+            let data = ssao::Data {
+                vbuf: blit.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                kernel: kernel,
+                tex_pos: (gpos.resource.clone(), sampler.clone()),
+                tex_normal: (gnormal.resource.clone(), sampler.clone()),
+                out: ao.target.clone(),
+            };
+
+            ssao::bundle(blit.slice.clone(), pso, data)
+        };
+
+        let ssao_blur = {
+            let ps = gfx_app::shade::Source {
+                glsl_150: SSAO_BLUR_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/ssao_blur_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "ssao_blur",
+                vs_fullscreen.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, ssao_blur::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = ssao_blur::Data {
+                vbuf: blit.data.vbuf.clone(),
+                tex: (ao.resource.clone(), sampler.clone()),
+                out: ao_blurred.target.clone(),
+            };
+
+            ssao_blur::bundle(blit.slice.clone(), pso, data)
+        };
+
+        let linear_sampler = factory.create_sampler(
+            gfx::tex::SamplerInfo::new(gfx::tex::FilterMethod::Bilinear,
+                                       gfx::tex::WrapMode::Clamp)
+        );
+
+        let bloom_targets = [
+            {
+                let (_ , srv, rtv) = factory.create_render_target(width / 2, height / 2).unwrap();
+                ViewPair{ resource: srv, target: rtv }
+            },
+            {
+                let (_ , srv, rtv) = factory.create_render_target(width / 2, height / 2).unwrap();
+                ViewPair{ resource: srv, target: rtv }
+            },
+        ];
+
+        let post_vs = gfx_app::shade::Source {
+            glsl_150: BLIT_VERTEX_SRC,
+            hlsl_40:  include_bytes!("data/post_vs.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+
+        let bloom = {
+            let ps = gfx_app::shade::Source {
+                glsl_150: BLOOM_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/bloom_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "bloom",
+                post_vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, bloom::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = bloom::Data {
+                vbuf: blit.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                tex: (res.resource.clone(), linear_sampler.clone()),
+                out: bloom_targets[0].target.clone(),
+            };
+
+            bloom::bundle(blit.slice.clone(), pso, data)
+        };
+
+        let thumbnail = {
+            let vs = gfx_app::shade::Source {
+                glsl_150: THUMBNAIL_VERTEX_SRC,
+                hlsl_40:  include_bytes!("data/thumbnail_vs.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+            let ps = gfx_app::shade::Source {
+                glsl_150: THUMBNAIL_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/thumbnail_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "thumbnail",
+                vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, thumbnail::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = thumbnail::Data {
+                vbuf: blit.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                tex: (gpos.resource.clone(), linear_sampler.clone()),
+                out: init.color.clone(),
+            };
+
+            thumbnail::bundle(blit.slice.clone(), pso, data)
+        };
+
+        let tonemap = {
+            let ps = gfx_app::shade::Source {
+                glsl_150: TONEMAP_FRAGMENT_SRC,
+                hlsl_40:  include_bytes!("data/tonemap_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "tonemap",
+                post_vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing, tonemap::new()
+                ).unwrap();
+            reflections.push(reflection);
+
+            let data = tonemap::Data {
+                vbuf: blit.data.vbuf.clone(),
+                locals: factory.create_constant_buffer(1),
+                tex_hdr: (res.resource.clone(), linear_sampler.clone()),
+                tex_bloom: (bloom_targets[0].resource.clone(), linear_sampler.clone()),
+                out: blit.data.out.clone(),
+            };
+
+            tonemap::bundle(blit.slice.clone(), pso, data)
+        };
+
         let light_pos_buffer = factory.create_constant_buffer(NUM_LIGHTS);
 
         let (light_vbuf, mut light_slice) = {
@@ -563,11 +1520,12 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 .. gfx_app::shade::Source::empty()
             };
 
-            let pso = factory.create_pipeline_simple(
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "light",
                 vs.select(init.backend).unwrap(),
                 ps.select(init.backend).unwrap(),
                 gfx::state::CullFace::Back, light::new()
                 ).unwrap();
+            reflections.push(reflection);
 
             let data = light::Data {
                 vbuf: light_vbuf.clone(),
@@ -577,6 +1535,7 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 tex_pos: (gpos.resource.clone(), sampler.clone()),
                 tex_normal: (gnormal.resource.clone(), sampler.clone()),
                 tex_diffuse: (gdiffuse.resource.clone(), sampler.clone()),
+                tex_occlusion: (ao_blurred.resource.clone(), sampler.clone()),
                 out_color: res.target.clone(),
                 out_depth: depth_target.clone(),
             };
@@ -596,11 +1555,12 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 .. gfx_app::shade::Source::empty()
             };
 
-            let pso = factory.create_pipeline_simple(
+            let (pso, reflection) = reflect::create_pipeline(&mut factory, "emitter",
                 vs.select(init.backend).unwrap(),
                 ps.select(init.backend).unwrap(),
                 gfx::state::CullFace::Back, emitter::new()
                 ).unwrap();
+            reflections.push(reflection);
 
             let data = emitter::Data {
                 vbuf: light_vbuf.clone(),
@@ -613,34 +1573,77 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
             emitter::bundle(light_slice, pso, data)
         };
 
-        let buffers = vec![None, Some(gpos.resource.clone()), Some(gnormal.resource.clone()), Some(gdiffuse.resource.clone())];
+        let buffers = vec![None, Some(gpos.resource.clone()), Some(gnormal.resource.clone()), Some(gdiffuse.resource.clone()),
+                           Some(ao_blurred.resource.clone())];
+
+        // the buffers `<gfx-image>` elements of the document can show
+        let mut images = ImageRegistry::new();
+        images.register("gpos", gpos.resource.clone());
+        images.register("gnormal", gnormal.resource.clone());
+        images.register("gdiffuse", gdiffuse.resource.clone());
+        images.register("ssao", ao_blurred.resource.clone());
 
         App {
             terrain: terrain,
             blit: blit,
+            thumbnail: thumbnail,
             light: light,
             emitter: emitter,
+            shadow: shadow,
+            sun: sun,
+            shadow_maps: shadow_maps,
+            sun_params: Rc::new(Cell::new(SunParams::default())),
+            camera_params: Rc::new(Cell::new(CameraParams::default())),
+            light_params: Rc::new(Cell::new(LightParams::default())),
+            bloom: bloom,
+            tonemap: tonemap,
+            bloom_targets: bloom_targets,
+            hdr_params: Rc::new(Cell::new(HdrParams::default())),
+            ssao: ssao,
+            ssao_blur: ssao_blur,
+            ssao_params: Rc::new(Cell::new(SsaoParams::default())),
+            ssao_kernel_dirty: true,
+            chunks: chunks,
             intermediate: res,
             light_pos_vec: (0 ..NUM_LIGHTS).map(|_| {
                 LightInfo{ pos: [0.0, 0.0, 0.0, 0.0] }
             }).collect(),
-            seed: seed,
+            seed: Seed::new(params.seed),
+            terrain_params: Rc::new(Cell::new(params)),
+            color_ramp: Rc::new(RefCell::new(ColorRamp::default())),
+            regenerate: Rc::new(Cell::new(true)),
+            terrain_mesh: Rc::new(RefCell::new(TerrainMesh::default())),
+            constants: ConstantBlocks::new(),
             // debug_buf: None,
             debug_buffers: buffers,
             buf_num: Rc::new(Cell::new(0)),
+            images: images,
+            reflections: reflections,
+            graph: Rc::new(render_graph()),
             view: None,
         }
     }
 
     fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
+        if self.ssao_kernel_dirty {
+            self.ssao_kernel_dirty = false;
+            encoder.update_buffer(&self.ssao.data.kernel, &generate_ssao_kernel(), 0).unwrap();
+        }
+        if self.regenerate.get() {
+            self.regenerate.set(false);
+            self.rebuild_terrain(encoder);
+        }
+        let params = self.terrain_params.get();
+
         let time = precise_time_s() as f32;
 
         // Update camera position
+        let camera = self.camera_params.get();
         let cam_pos = {
             // Slowly circle the center
-            let x = (0.05*time).sin();
-            let y = (0.05*time).cos();
-            Point3::new(x * 32.0, y * 32.0, 16.0)
+            let x = (camera.speed*time).sin();
+            let y = (camera.speed*time).cos();
+            Point3::new(x * camera.distance, y * camera.distance, camera.height)
         };
         let view: AffineMatrix3<f32> = Transform::look_at(
             cam_pos,
@@ -649,67 +1652,104 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
         );
         let (width, height, _, _) = self.terrain.data.out_depth.get_dimensions();
         let aspect = width as f32 / height as f32;
-        let proj = cgmath::perspective(deg(60.0f32), aspect, 5.0, 100.0);
+        let proj = cgmath::perspective(deg(camera.fov), aspect, 5.0, 100.0);
+        self.chunks.update(cam_pos, proj * view.mat);
 
         let terrain_locals = TerrainLocals {
             model: Matrix4::identity().into(),
             view: view.mat.into(),
             proj: proj.into(),
         };
-        encoder.update_constant_buffer(&self.terrain.data.locals, &terrain_locals);
+        self.constants.terrain.borrow_mut().update(encoder, &self.terrain.data.locals, terrain_locals);
 
         let light_locals = LightLocals {
             cam_pos_and_radius: [cam_pos.x, cam_pos.y, cam_pos.z,
                 1.0 / (LIGHT_RADIUS * LIGHT_RADIUS)],
         };
-        encoder.update_buffer(&self.light.data.locals_ps, &[light_locals], 0).unwrap();
+        self.constants.light.borrow_mut().update(encoder, &self.light.data.locals_ps, light_locals);
 
-        let mut cube_locals = CubeLocals {
+        let cube_locals = CubeLocals {
             transform: (proj * view.mat).into(),
             radius: LIGHT_RADIUS,
         };
-        encoder.update_constant_buffer(&self.light.data.locals_vs, &cube_locals);
+        let mut cube_locals = self.constants.light_volume.borrow_mut().update(encoder, &self.light.data.locals_vs, cube_locals);
         cube_locals.radius = EMITTER_RADIUS;
         encoder.update_constant_buffer(&self.emitter.data.locals, &cube_locals);
 
         // Update light positions
+        let lights = self.light_params.get();
         for (i, d) in self.light_pos_vec.iter_mut().enumerate() {
             let (x, y) = {
                 let fi = i as f32;
                 // Distribute lights nicely
                 let r = 1.0 - (fi*fi) / ((NUM_LIGHTS*NUM_LIGHTS) as f32);
-                (r * (0.2*time + i as f32).cos(), r * (0.2*time + i as f32).sin())
+                (r * (lights.speed*time + i as f32).cos(), r * (lights.speed*time + i as f32).sin())
             };
-            let h = perlin2(&self.seed, &[x, y]);
+            let h = params.height(&self.seed, x, y);
 
-            d.pos[0] = TERRAIN_SCALE[0] * x;
-            d.pos[1] = TERRAIN_SCALE[1] * y;
-            d.pos[2] = TERRAIN_SCALE[2] * h + 0.5;
+            d.pos[0] = params.scale * x;
+            d.pos[1] = params.scale * y;
+            d.pos[2] = h + lights.height;
         };
         encoder.update_buffer(&self.light.data.light_pos_buf, &self.light_pos_vec, 0).unwrap();
 
-        encoder.clear_depth(&self.terrain.data.out_depth, 1.0);
-        encoder.clear(&self.terrain.data.out_position, [0.0, 0.0, 0.0, 1.0]);
-        encoder.clear(&self.terrain.data.out_normal, [0.0, 0.0, 0.0, 1.0]);
-        encoder.clear(&self.terrain.data.out_color, [0.0, 0.0, 0.0, 1.0]);
-        // Render the terrain to the geometry buffer
-        self.terrain.encode(encoder);
+        // Update the sun and pick the shadow map
+        let sun_params = self.sun_params.get();
+        let light_transform = sun_params.light_transform(&params);
+        let shadow_map = &self.shadow_maps[sun_params.shadow_map.min(self.shadow_maps.len() - 1)];
+        self.shadow.data.out_depth = shadow_map.target.clone();
+        self.sun.data.tex_shadow.0 = shadow_map.resource.clone();
+
+        encoder.update_constant_buffer(&self.shadow.data.locals, &ShadowLocals {
+            transform: light_transform.into(),
+        });
+        let sun_dir = sun_params.direction();
+        let sun_locals = SunLocals {
+            light_transform: light_transform.into(),
+            direction: [sun_dir.x, sun_dir.y, sun_dir.z, 0.0],
+            color: [sun_params.intensity, sun_params.intensity, 0.9 * sun_params.intensity, sun_params.ambient],
+            shadow_params: [sun_params.bias, 1.0 / shadow_map.size as f32, 0.0, 0.0],
+        };
+        self.constants.sun.borrow_mut().update(encoder, &self.sun.data.locals, sun_locals);
 
+        let ssao_params = self.ssao_params.get();
+        self.constants.ssao.borrow_mut().update(encoder, &self.ssao.data.locals, SsaoLocals {
+            view: view.mat.into(),
+            proj: proj.into(),
+            params: [ssao_params.radius, ssao_params.samples as f32, ssao_params.strength, 0.0],
+        });
+
+        let hdr = self.hdr_params.get();
+        let tone_mapping = match hdr.tone_mapping {
+            ToneMapping::Clamp => 0.0,
+            ToneMapping::Reinhard => 1.0,
+            ToneMapping::Aces => 2.0,
+        };
+        let bloom_strength = if hdr.bloom { hdr.bloom_strength } else { 0.0 };
+        self.constants.tonemap.borrow_mut().update(encoder, &self.tonemap.data.locals, TonemapLocals {
+            params: [hdr.exposure, tone_mapping, bloom_strength, 0.0],
+        });
+
+        // Show one of the immediate buffers, if picked
         let buf_num = self.buf_num.get() as usize;
-        let blit_tex = match self.debug_buffers[buf_num] {
-            Some(ref tex) => tex,   // Show one of the immediate buffers
-            None => {
-                encoder.clear(&self.intermediate.target, [0.0, 0.0, 0.0, 1.0]);
-                // Apply lights
-                self.light.encode(encoder);
-                // Draw light emitters
-                self.emitter.encode(encoder);
-                &self.intermediate.resource
-            }
+        let debug_buffer = match self.debug_buffers[buf_num].clone() {
+            Some(tex) => {
+                self.blit.data.tex.0 = tex;
+                true
+            },
+            None => false,
         };
-        self.blit.data.tex.0 = blit_tex.clone();
-        // Show the result
-        self.blit.encode(encoder);
+
+        let graph = self.graph.clone();
+        for pass in graph.passes() {
+            if !pass_enabled(pass.name, debug_buffer, hdr.bloom) {
+                continue;
+            }
+            for resource in &pass.clears {
+                self.clear_resource(encoder, resource);
+            }
+            self.encode_pass(encoder, pass.name, &hdr);
+        }
     }
 
     fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
@@ -726,22 +1766,88 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
       let host = any.downcast_ref::<SciterHost>().unwrap();
 
       // load UI from html
-      let ui = include_bytes!("facade.htm");
-      host.load_html(ui, None);
+      let ui = inspect::with_inspector(include_str!("facade.htm"));
+      host.load_html(ui.as_bytes(), None);
 
       // attach root handler
       if let Some(root) = host.get_root() {
         println!("document loaded: {}", root);
 
         let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
-        self.view = Some(View { api: api, hwnd: host.get_hwnd() });
-
-        let handler = Handler { host: Rc::downgrade(&host.clone()), buf_num: self.buf_num.clone() };
+        self.view = Some(View { api: api, hwnd: host.get_hwnd(), root: root.clone() });
+
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          buf_num: self.buf_num.clone(),
+          graph: self.graph.clone(),
+          terrain_params: self.terrain_params.clone(),
+          color_ramp: self.color_ramp.clone(),
+          regenerate: self.regenerate.clone(),
+          terrain_mesh: self.terrain_mesh.clone(),
+          sun_params: self.sun_params.clone(),
+          hdr_params: self.hdr_params.clone(),
+          ssao_params: self.ssao_params.clone(),
+          scene: Scene {
+            terrain: TerrainNode { params: self.terrain_params.clone(), regenerate: self.regenerate.clone() },
+            lights: self.light_params.clone(),
+            camera: self.camera_params.clone(),
+            constants: self.constants.clone(),
+          },
+        };
         host.attach_handler(handler);
 
-        let blends: sciter::Value = ["none", "gpos", "gnormal", "gdiffuse"].iter().cloned().collect();
+        let blends: sciter::Value = ["none", "gpos", "gnormal", "gdiffuse", "ssao"].iter().cloned().collect();
         root.call_function("setupBlending", &[blends]).ok();
 
+        let params = self.terrain_params.get();
+        root.call_function("setupTerrain", &[
+          sciter::Value::from(params.seed as i32),
+          sciter::Value::from(params.octaves as i32),
+          sciter::Value::from(params.frequency as f64),
+          sciter::Value::from(params.amplitude as f64),
+          sciter::Value::from(params.scale as f64),
+          sciter::Value::from(params.resolution as i32),
+        ]).ok();
+
+        let ramp = ramp_to_value(&self.color_ramp.borrow());
+        root.call_function("setupColorRamp", &[ramp]).ok();
+
+        let sun = self.sun_params.get();
+        let sizes: sciter::Value = SHADOW_MAP_SIZES.iter().map(|&size| size as i32).collect();
+        root.call_function("setupSun", &[
+          sciter::Value::from(sun.azimuth as f64),
+          sciter::Value::from(sun.elevation as f64),
+          sciter::Value::from(sun.bias as f64),
+          sizes,
+          sciter::Value::from(sun.shadow_map as i32),
+        ]).ok();
+
+        let hdr = self.hdr_params.get();
+        let tone_mappings: sciter::Value = TONE_MAPPINGS.iter().map(|&(name, _)| name).collect();
+        let tone_mapping = TONE_MAPPINGS.iter().position(|&(_, op)| op == hdr.tone_mapping).unwrap();
+        root.call_function("setupHdr", &[
+          sciter::Value::from(hdr.exposure as f64),
+          tone_mappings,
+          sciter::Value::from(tone_mapping as i32),
+          sciter::Value::from(hdr.bloom),
+          sciter::Value::from(hdr.bloom_threshold as f64),
+          sciter::Value::from(hdr.bloom_strength as f64),
+        ]).ok();
+
+        let ssao = self.ssao_params.get();
+        root.call_function("setupSsao", &[
+          sciter::Value::from(ssao.radius as f64),
+          sciter::Value::from(ssao.samples as i32),
+          sciter::Value::from(MAX_SSAO_SAMPLES as i32),
+          sciter::Value::from(ssao.strength as f64),
+        ]).ok();
+
+        let pipelines: sciter::Value = self.reflections.iter().map(|r| r.to_value()).collect();
+        root.call_function("setupPipelines", &[pipelines]).ok();
+
+        root.call_function("showRenderGraph", &[]).ok();
+        root.call_function("inspectorRefresh", &[]).ok();
+
       } else {
         println!("oops: no root element!");
       }
@@ -751,6 +1857,101 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
 
 impl<R: gfx::Resources> App<R> {
 
+  fn rebuild_terrain<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
+    let params = self.terrain_params.get().clamped();
+    self.terrain_params.set(params);
+    self.seed = Seed::new(params.seed);
+
+    let vertex_data = generate_terrain(&params, &self.color_ramp.borrow());
+    encoder.update_buffer(&self.terrain.data.vbuf, &vertex_data, 0).unwrap();
+    let heights = vertex_data.chunks(CHUNK_VERTICES).map(|chunk| {
+      chunk.iter().fold((::std::f32::MAX, ::std::f32::MIN), |(low, high), v| (low.min(v.pos[2]), high.max(v.pos[2])))
+    }).collect();
+    self.chunks.resize(params.resolution / CHUNK_CELLS, 2.0 * params.scale, heights);
+    let index_data = self.chunks.full_indices();
+    *self.terrain_mesh.borrow_mut() = TerrainMesh { vertices: vertex_data, indices: index_data };
+  }
+
+  fn clear_resource<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>, resource: &str) {
+    match resource {
+      "shadow map" => encoder.clear_depth(&self.shadow.data.out_depth, 1.0),
+      "depth" => encoder.clear_depth(&self.terrain.data.out_depth, 1.0),
+      "position" => encoder.clear(&self.terrain.data.out_position, [0.0, 0.0, 0.0, 1.0]),
+      "normal" => encoder.clear(&self.terrain.data.out_normal, [0.0, 0.0, 0.0, 1.0]),
+      "diffuse" => encoder.clear(&self.terrain.data.out_color, [0.0, 0.0, 0.0, 1.0]),
+      "hdr" => encoder.clear(&self.intermediate.target, [0.0, 0.0, 0.0, 1.0]),
+      _ => panic!("unknown resource {}", resource),
+    }
+  }
+
+  fn encode_pass<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>, pass: &str, hdr: &HdrParams) {
+    match pass {
+      // Render the terrain depth from the sun
+      "shadow" => {
+        for slice in self.chunks.slices() {
+          encoder.draw(slice, &self.shadow.pso, &self.shadow.data);
+        }
+      },
+      // Render the terrain to the geometry buffer, chunk by chunk, skipping the ones out of view
+      "terrain" => {
+        for slice in self.chunks.visible_slices() {
+          encoder.draw(slice, &self.terrain.pso, &self.terrain.data);
+        }
+      },
+      // Estimate and blur the ambient occlusion
+      "ssao" => self.ssao.encode(encoder),
+      "ssao_blur" => self.ssao_blur.encode(encoder),
+      "sun" => self.sun.encode(encoder),
+      "light" => self.light.encode(encoder),
+      "emitter" => self.emitter.encode(encoder),
+      "bloom" => self.encode_bloom(encoder, hdr),
+      "tonemap" => self.tonemap.encode(encoder),
+      "blit" => self.blit.encode(encoder),
+      "thumbnails" => self.encode_thumbnails(encoder),
+      _ => panic!("unknown pass {}", pass),
+    }
+  }
+
+  fn encode_bloom<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>, hdr: &HdrParams) {
+    let (width, height, _, _) = self.bloom_targets[0].target.get_dimensions();
+    let mut locals = BloomLocals {
+      params: [hdr.bloom_threshold, 0.0, 1.0 / width as f32, 1.0 / height as f32],
+    };
+
+    // bright pass, downsampling the result buffer into the first bloom target
+    self.bloom.data.tex.0 = self.intermediate.resource.clone();
+    self.bloom.data.out = self.bloom_targets[0].target.clone();
+    encoder.update_constant_buffer(&self.bloom.data.locals, &locals);
+    self.bloom.encode(encoder);
+
+    // separable gaussian blur, ping-ponging between the bloom targets
+    for _ in 0..hdr.bloom_passes {
+      for &(pass, src, dst) in &[(1.0, 0, 1), (2.0, 1, 0)] {
+        locals.params[1] = pass;
+        self.bloom.data.tex.0 = self.bloom_targets[src].resource.clone();
+        self.bloom.data.out = self.bloom_targets[dst].target.clone();
+        encoder.update_constant_buffer(&self.bloom.data.locals, &locals);
+        self.bloom.encode(encoder);
+      }
+    }
+  }
+
+  // Draw the registered buffers over the boxes of the `<gfx-image>` elements
+  fn encode_thumbnails<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
+    let images = match self.view {
+      Some(ref view) => self.images.images(view.api, &view.root),
+      None => return,
+    };
+    let (width, height, _, _) = self.thumbnail.data.out.get_dimensions();
+    for (rect, tex) in images {
+      encoder.update_constant_buffer(&self.thumbnail.data.locals, &ThumbnailLocals {
+        rect: rect.to_ndc(width, height),
+      });
+      self.thumbnail.data.tex.0 = tex;
+      self.thumbnail.encode(encoder);
+    }
+  }
+
   fn render_document(&mut self) {
     if self.view.is_none() {
       return;
@@ -767,6 +1968,32 @@ impl<R: gfx::Resources> App<R> {
 struct Handler {
   host: Weak<sciter::Host>,
   buf_num: Rc<Cell<u8>>,
+  graph: Rc<RenderGraph>,
+  terrain_params: Rc<Cell<TerrainParams>>,
+  color_ramp: Rc<RefCell<ColorRamp>>,
+  regenerate: Rc<Cell<bool>>,
+  terrain_mesh: Rc<RefCell<TerrainMesh>>,
+  sun_params: Rc<Cell<SunParams>>,
+  hdr_params: Rc<Cell<HdrParams>>,
+  ssao_params: Rc<Cell<SsaoParams>>,
+  scene: Scene,
+}
+
+// stops travel to and from the script as `[[pos, r, g, b], ...]`
+fn ramp_to_value(ramp: &ColorRamp) -> sciter::Value {
+  ramp.stops.iter().map(|stop| {
+    let c = stop.color;
+    [stop.pos, c[0], c[1], c[2]].iter().map(|&x| x as f64).collect::<sciter::Value>()
+  }).collect()
+}
+
+fn ramp_from_value(stops: &sciter::Value) -> ColorRamp {
+  let stops = (0..stops.len()).map(|i| {
+    let stop = stops.get(i);
+    let item = |n| to_number(&stop.get(n)).unwrap_or(0.0) as f32;
+    ColorStop { pos: item(0), color: [item(1), item(2), item(3)] }
+  }).collect();
+  ColorRamp::new(stops)
 }
 
 impl sciter::EventHandler for Handler {
@@ -775,11 +2002,123 @@ impl sciter::EventHandler for Handler {
   fn on_script_call(&mut self, root: sciter::HELEMENT, name: &str, args: &[sciter::Value]) -> Option<sciter::Value> {
     let ok = sciter::Value::from(true);
     match name {
+      "inspect" => {
+        Some(inspect::to_value("scene", &self.scene))
+      },
+      "setProperty" => {
+        let path: Vec<String> = args[0].as_string().unwrap().split('/')
+          .filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+        let name = args[1].as_string().unwrap();
+        Some(sciter::Value::from(inspect::set_property(&mut self.scene, &path, &name, &args[2])))
+      },
+      "renderGraph" => {
+        // the first buffer is the final image
+        let debug_buffer = self.buf_num.get() != 0;
+        let bloom = self.hdr_params.get().bloom;
+        Some(self.graph.to_value(&|name| pass_enabled(name, debug_buffer, bloom)))
+      },
       "setBlending" => {
         let id = args[0].to_int().unwrap();
         self.buf_num.set(id as u8);
         Some(ok)
       },
+      "regenerateTerrain" => {
+        // a malformed call is left unhandled
+        let int = |i: usize| args.get(i).and_then(|value| value.to_int());
+        let number = |i: usize| args.get(i).and_then(to_number);
+        let params = match (int(0), int(1), number(2), number(3), number(4), int(5)) {
+          (Some(seed), Some(octaves), Some(frequency), Some(amplitude), Some(scale), Some(resolution)) => TerrainParams {
+            seed: seed.max(0) as u32,
+            octaves: octaves.max(1) as u32,
+            frequency: frequency as f32,
+            amplitude: amplitude as f32,
+            scale: scale as f32,
+            resolution: resolution.max(0) as usize,
+          },
+          _ => return None,
+        };
+        self.terrain_params.set(params.clamped());
+        self.regenerate.set(true);
+        Some(ok)
+      },
+      "setSun" => {
+        let sun = SunParams {
+          azimuth: to_number(&args[0]).unwrap() as f32,
+          elevation: (to_number(&args[1]).unwrap() as f32).max(5.0).min(85.0),
+          bias: to_number(&args[2]).unwrap() as f32,
+          shadow_map: (args[3].to_int().unwrap() as usize).min(SHADOW_MAP_SIZES.len() - 1),
+          .. self.sun_params.get()
+        };
+        self.sun_params.set(sun);
+        Some(ok)
+      },
+      "setHdr" => {
+        let tone_mapping = (args[1].to_int().unwrap() as usize).min(TONE_MAPPINGS.len() - 1);
+        let hdr = HdrParams {
+          exposure: to_number(&args[0]).unwrap() as f32,
+          tone_mapping: TONE_MAPPINGS[tone_mapping].1,
+          bloom: args[2].to_bool().unwrap(),
+          bloom_threshold: to_number(&args[3]).unwrap() as f32,
+          bloom_strength: to_number(&args[4]).unwrap() as f32,
+          .. self.hdr_params.get()
+        };
+        self.hdr_params.set(hdr);
+        Some(ok)
+      },
+      "setSsao" => {
+        let ssao = SsaoParams {
+          radius: to_number(&args[0]).unwrap() as f32,
+          samples: (args[1].to_int().unwrap() as u32).max(1).min(MAX_SSAO_SAMPLES as u32),
+          strength: to_number(&args[2]).unwrap() as f32,
+        };
+        self.ssao_params.set(ssao);
+        Some(ok)
+      },
+      "exportTerrain" => {
+        let path = args[0].as_string().unwrap();
+        let path = path_from_url(&path);
+        let path = std::path::Path::new(&path);
+        match self.terrain_mesh.borrow().export(path) {
+          Ok(_) => {
+            println!("terrain exported to {}", path.display());
+            Some(ok)
+          },
+          Err(e) => {
+            println!("unable to export the terrain to {}: {}", path.display(), e);
+            Some(sciter::Value::from(false))
+          }
+        }
+      },
+      "setColorRamp" => {
+        *self.color_ramp.borrow_mut() = ramp_from_value(&args[0]);
+        self.regenerate.set(true);
+        Some(ok)
+      },
+      "saveColorRamp" => {
+        let path = args[0].as_string().unwrap();
+        match self.color_ramp.borrow().save(&path_from_url(&path)) {
+          Ok(_) => Some(ok),
+          Err(e) => {
+            println!("unable to save the color ramp to {}: {}", path, e);
+            Some(sciter::Value::from(false))
+          }
+        }
+      },
+      "loadColorRamp" => {
+        let path = args[0].as_string().unwrap();
+        match ColorRamp::load(&path_from_url(&path)) {
+          Ok(ramp) => {
+            let stops = ramp_to_value(&ramp);
+            *self.color_ramp.borrow_mut() = ramp;
+            self.regenerate.set(true);
+            Some(stops)
+          },
+          Err(e) => {
+            println!("unable to load the color ramp from {}: {}", path, e);
+            Some(sciter::Value::new())
+          }
+        }
+      },
       _ => None,
     }
   }
@@ -0,0 +1,102 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Terrain color ramp.
+//
+// A ramp is a list of color stops over the normalized terrain height:
+// 0.0 is the lowest point the noise can reach, 1.0 is the highest one.
+// Two stops at the same position give a hard edge between bands.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use rustc_serialize::json;
+
+#[derive(Clone, Copy, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct ColorStop {
+    pub pos: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct ColorRamp {
+    pub stops: Vec<ColorStop>,
+}
+
+impl Default for ColorRamp {
+    // the classic four bands: blue, green, grey and white
+    fn default() -> ColorRamp {
+        let blue = [0.2, 0.2, 0.7];
+        let green = [0.2, 0.7, 0.2];
+        let grey = [0.7, 0.7, 0.7];
+        let white = [0.9, 0.9, 0.9];
+        ColorRamp::new(vec![
+            ColorStop { pos: 0.0, color: blue },
+            ColorStop { pos: 0.4, color: blue },
+            ColorStop { pos: 0.4, color: green },
+            ColorStop { pos: 0.5, color: green },
+            ColorStop { pos: 0.5, color: grey },
+            ColorStop { pos: 0.66, color: grey },
+            ColorStop { pos: 0.66, color: white },
+            ColorStop { pos: 1.0, color: white },
+        ])
+    }
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<ColorStop>) -> ColorRamp {
+        for stop in stops.iter_mut() {
+            stop.pos = stop.pos.max(0.0).min(1.0);
+        }
+        stops.sort_by(|a, b| a.pos.partial_cmp(&b.pos).unwrap());
+        ColorRamp { stops: stops }
+    }
+
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let first = match self.stops.first() {
+            Some(stop) => stop,
+            None => return [0.0; 3],
+        };
+        if t <= first.pos {
+            return first.color;
+        }
+        for pair in self.stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if t <= b.pos {
+                let span = b.pos - a.pos;
+                let k = if span > 0.0 { (t - a.pos) / span } else { 1.0 };
+                return [
+                    a.color[0] + k * (b.color[0] - a.color[0]),
+                    a.color[1] + k * (b.color[1] - a.color[1]),
+                    a.color[2] + k * (b.color[2] - a.color[2]),
+                ];
+            }
+        }
+        self.stops[self.stops.len() - 1].color
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text = json::encode(self).unwrap();
+        let mut file = try!(File::create(path));
+        file.write_all(text.as_bytes())
+    }
+
+    pub fn load(path: &str) -> io::Result<ColorRamp> {
+        let mut text = String::new();
+        try!(File::open(path).and_then(|mut file| file.read_to_string(&mut text)));
+        match json::decode::<ColorRamp>(&text) {
+            Ok(ramp) => Ok(ColorRamp::new(ramp.stops)),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}", e))),
+        }
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// glTF 2.0 loading.
+//
+// Reads `.gltf` documents whose buffers and images are either embedded as base64 `data:`
+// URIs or stored in files next to the document. Binary `.glb` containers, sparse accessors,
+// skins, morph targets and animations are not supported, and only triangle lists are kept.
+//
+// Matrices are `[[f32; 4]; 4]` in column major order, as in the document and the shaders.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use byteorder::{ByteOrder, LittleEndian};
+use image::{self, RgbaImage};
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
+use gfx_sciter_examples::percent_decode;
+
+use Vertex;
+
+pub type Matrix = [[f32; 4]; 4];
+
+pub const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 4]; 4];
+    for c in 0..4 {
+        for r in 0..4 {
+            out[c][r] = (0..4).fold(0.0, |sum, k| sum + a[k][r] * b[c][k]);
+        }
+    }
+    out
+}
+
+pub fn transform_point(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    let mut out = [0.0; 3];
+    for r in 0..3 {
+        out[r] = m[0][r] * p[0] + m[1][r] * p[1] + m[2][r] * p[2] + m[3][r];
+    }
+    out
+}
+
+/// The metallic-roughness model, without the normal, occlusion and emissive maps.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub base_color: [f32; 4],
+    /// Index into `Document::images`.
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl Material {
+    /// Used for the primitives without a material.
+    pub fn fallback() -> Material {
+        Material {
+            name: "default".to_string(),
+            base_color: [0.8, 0.8, 0.8, 1.0],
+            base_color_texture: None,
+            metallic: 0.0,
+            roughness: 0.5,
+        }
+    }
+}
+
+pub struct Primitive {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub primitives: Vec<Primitive>,
+}
+
+pub struct Node {
+    pub name: String,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+    /// Transform relative to the parent.
+    pub matrix: Matrix,
+}
+
+pub struct Document {
+    pub nodes: Vec<Node>,
+    /// The nodes of the default scene.
+    pub roots: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub images: Vec<RgbaImage>,
+}
+
+fn array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+    json.find(key).and_then(|v| v.as_array()).map_or(&[], |v| &v[..])
+}
+
+fn index(json: &Json, key: &str) -> Option<usize> {
+    json.find(key).and_then(|v| v.as_u64()).map(|v| v as usize)
+}
+
+fn number(json: &Json, key: &str, default: f32) -> f32 {
+    json.find(key).and_then(|v| v.as_f64()).map_or(default, |v| v as f32)
+}
+
+fn numbers(json: &Json, key: &str) -> Option<Vec<f32>> {
+    json.find(key).and_then(|v| v.as_array())
+        .map(|v| v.iter().map(|x| x.as_f64().unwrap_or(0.0) as f32).collect())
+}
+
+// the numbers of `key`, or the default when they are missing or not as many
+fn numbers_or(json: &Json, key: &str, default: &[f32]) -> Vec<f32> {
+    match numbers(json, key) {
+        Some(v) if v.len() == default.len() => v,
+        _ => default.to_vec(),
+    }
+}
+
+fn name(json: &Json, default: String) -> String {
+    json.find("name").and_then(|v| v.as_string()).map_or(default, |s| s.to_string())
+}
+
+fn element<'a>(json: &'a Json, key: &str, i: usize) -> Result<&'a Json, String> {
+    array(json, key).get(i).ok_or(format!("{} {} is missing", key, i))
+}
+
+// the contents of a `data:` URI, or of a file relative to the document, its URI being escaped
+fn read_uri(uri: &str, base: Option<&Path>) -> Result<Vec<u8>, String> {
+    if uri.starts_with("data:") {
+        return match uri.find(";base64,") {
+            Some(start) => uri[start + 8..].from_base64().map_err(|e| format!("{}", e)),
+            None => Err("only base64 data URIs are supported".to_string()),
+        };
+    }
+    let path = match base {
+        Some(base) => base.join(percent_decode(uri)),
+        None => return Err(format!("no directory to look for {} in", uri)),
+    };
+    let mut data = Vec::new();
+    try!(File::open(&path).and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|e| format!("{}: {}", path.display(), e)));
+    Ok(data)
+}
+
+// the elements of an accessor within its buffer view
+struct Accessor<'a> {
+    data: &'a [u8],
+    count: usize,
+    size: usize,
+    stride: usize,
+    offset: usize,
+    component: usize,
+    bytes: usize,
+    normalized: bool,
+}
+
+struct Loader<'a> {
+    json: &'a Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Loader<'a> {
+    fn buffer_view(&self, i: usize) -> Result<&[u8], String> {
+        let view = try!(element(self.json, "bufferViews", i));
+        let buffer = try!(index(view, "buffer").and_then(|b| self.buffers.get(b)).ok_or("bad buffer view".to_string()));
+        let offset = index(view, "byteOffset").unwrap_or(0);
+        let length = try!(index(view, "byteLength").ok_or("buffer view without a length".to_string()));
+        buffer.get(offset .. offset + length).ok_or(format!("buffer view {} is out of its buffer", i))
+    }
+
+    /// Locates the elements of an accessor of `components` per element.
+    fn accessor(&self, i: usize, components: usize) -> Result<Accessor, String> {
+        let accessor = try!(element(self.json, "accessors", i));
+        if accessor.find("sparse").is_some() {
+            return Err("sparse accessors are not supported".to_string());
+        }
+        let count = index(accessor, "count").unwrap_or(0);
+        let kind = accessor.find("type").and_then(|t| t.as_string()).unwrap_or("");
+        let size = match kind { "SCALAR" => 1, "VEC2" => 2, "VEC3" => 3, "VEC4" => 4, _ => 0 };
+        if size != components {
+            return Err(format!("accessor {} is a {}, expected {} components", i, kind, components));
+        }
+        let component = index(accessor, "componentType").unwrap_or(0);
+        let bytes = match component {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(format!("accessor {} has an unknown component type {}", i, component)),
+        };
+        let view_index = try!(index(accessor, "bufferView").ok_or("accessors without a buffer view are not supported".to_string()));
+        let data = try!(self.buffer_view(view_index));
+        let view = try!(element(self.json, "bufferViews", view_index));
+        let stride = index(view, "byteStride").unwrap_or(bytes * size);
+        let offset = index(accessor, "byteOffset").unwrap_or(0);
+        if count > 0 && offset + (count - 1) * stride + bytes * size > data.len() {
+            return Err(format!("accessor {} is out of its buffer view", i));
+        }
+        Ok(Accessor {
+            data: data,
+            count: count,
+            size: size,
+            stride: stride,
+            offset: offset,
+            component: component,
+            bytes: bytes,
+            normalized: accessor.find("normalized").and_then(|n| n.as_boolean()).unwrap_or(false),
+        })
+    }
+
+    /// Reads an accessor as floats, `components` per element.
+    fn read(&self, i: usize, components: usize) -> Result<Vec<f32>, String> {
+        let a = try!(self.accessor(i, components));
+        let mut out = Vec::with_capacity(a.count * a.size);
+        for e in 0..a.count {
+            for c in 0..a.size {
+                let at = &a.data[a.offset + e * a.stride + c * a.bytes ..];
+                // normalized integers map to [0, 1], or [-1, 1] when signed
+                let value = match (a.component, a.normalized) {
+                    (5120, true) => (at[0] as i8 as f32 / 127.0).max(-1.0),
+                    (5120, false) => at[0] as i8 as f32,
+                    (5121, true) => at[0] as f32 / 255.0,
+                    (5121, false) => at[0] as f32,
+                    (5122, true) => (LittleEndian::read_i16(at) as f32 / 32767.0).max(-1.0),
+                    (5122, false) => LittleEndian::read_i16(at) as f32,
+                    (5123, true) => LittleEndian::read_u16(at) as f32 / 65535.0,
+                    (5123, false) => LittleEndian::read_u16(at) as f32,
+                    (5125, _) => LittleEndian::read_u32(at) as f32,
+                    _ => LittleEndian::read_f32(at),
+                };
+                out.push(value);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Reads an index accessor, without going through floats.
+    fn read_indices(&self, i: usize) -> Result<Vec<u32>, String> {
+        let a = try!(self.accessor(i, 1));
+        (0..a.count).map(|e| {
+            let at = &a.data[a.offset + e * a.stride ..];
+            match a.component {
+                5121 => Ok(at[0] as u32),
+                5123 => Ok(LittleEndian::read_u16(at) as u32),
+                5125 => Ok(LittleEndian::read_u32(at)),
+                _ => Err(format!("accessor {} has indices of component type {}", i, a.component)),
+            }
+        }).collect()
+    }
+
+    fn primitive(&self, json: &Json) -> Result<Primitive, String> {
+        let attributes = try!(json.find("attributes").ok_or("primitive without attributes".to_string()));
+        let positions = try!(self.read(try!(index(attributes, "POSITION").ok_or("primitive without positions".to_string())), 3));
+        let count = positions.len() / 3;
+        let normals = match index(attributes, "NORMAL") {
+            Some(i) => Some(try!(self.read(i, 3))),
+            None => None,
+        };
+        let uvs = match index(attributes, "TEXCOORD_0") {
+            Some(i) => Some(try!(self.read(i, 2))),
+            None => None,
+        };
+        let indices: Vec<u32> = match index(json, "indices") {
+            Some(i) => try!(self.read_indices(i)),
+            None => (0..count as u32).collect(),
+        };
+        if indices.iter().any(|&i| i as usize >= count) {
+            return Err("primitive index out of range".to_string());
+        }
+
+        let mut vertices: Vec<Vertex> = (0..count).map(|i| Vertex {
+            pos: [positions[3 * i], positions[3 * i + 1], positions[3 * i + 2]],
+            normal: normals.as_ref().map_or([0.0; 3], |n| [n[3 * i], n[3 * i + 1], n[3 * i + 2]]),
+            uv: uvs.as_ref().map_or([0.0; 2], |t| [t[2 * i], t[2 * i + 1]]),
+        }).collect();
+        if normals.is_none() {
+            // area weighted face normals
+            for tri in indices.chunks(3).filter(|tri| tri.len() == 3) {
+                let (a, b, c) = (vertices[tri[0] as usize].pos, vertices[tri[1] as usize].pos, vertices[tri[2] as usize].pos);
+                let (u, v) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+                let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+                for &i in tri {
+                    let normal = &mut vertices[i as usize].normal;
+                    for k in 0..3 {
+                        normal[k] += n[k];
+                    }
+                }
+            }
+        }
+
+        let (mut min, mut max) = ([::std::f32::MAX; 3], [::std::f32::MIN; 3]);
+        for v in &vertices {
+            for k in 0..3 {
+                min[k] = min[k].min(v.pos[k]);
+                max[k] = max[k].max(v.pos[k]);
+            }
+        }
+        Ok(Primitive {
+            vertices: vertices,
+            indices: indices,
+            material: index(json, "material"),
+            min: min,
+            max: max,
+        })
+    }
+
+    fn mesh(&self, i: usize, json: &Json) -> Result<Mesh, String> {
+        let mut primitives = Vec::new();
+        for primitive in array(json, "primitives") {
+            // 4 is a triangle list, the default
+            let mode = index(primitive, "mode").unwrap_or(4);
+            if mode != 4 {
+                println!("mesh {}: skipping a primitive of mode {}", i, mode);
+                continue;
+            }
+            primitives.push(try!(self.primitive(primitive)));
+        }
+        Ok(Mesh { name: name(json, format!("mesh {}", i)), primitives: primitives })
+    }
+
+    fn image(&self, json: &Json, base: Option<&Path>) -> Result<RgbaImage, String> {
+        let data = match (json.find("uri").and_then(|u| u.as_string()), index(json, "bufferView")) {
+            (Some(uri), _) => try!(read_uri(uri, base)),
+            (None, Some(view)) => try!(self.buffer_view(view)).to_vec(),
+            _ => return Err("image without data".to_string()),
+        };
+        image::load_from_memory(&data).map(|img| img.to_rgba()).map_err(|e| format!("{}", e))
+    }
+}
+
+fn node(i: usize, json: &Json) -> Node {
+    let matrix = match numbers(json, "matrix") {
+        Some(ref m) if m.len() == 16 => {
+            let mut out = [[0.0; 4]; 4];
+            for c in 0..4 {
+                for r in 0..4 {
+                    out[c][r] = m[4 * c + r];
+                }
+            }
+            out
+        },
+        _ => {
+            // translation * rotation * scale
+            let t = numbers_or(json, "translation", &[0.0; 3]);
+            let q = numbers_or(json, "rotation", &[0.0, 0.0, 0.0, 1.0]);
+            let s = numbers_or(json, "scale", &[1.0; 3]);
+            let (x, y, z, w) = (q[0], q[1], q[2], q[3]);
+            [
+                [s[0] * (1.0 - 2.0 * (y * y + z * z)), s[0] * 2.0 * (x * y + z * w), s[0] * 2.0 * (x * z - y * w), 0.0],
+                [s[1] * 2.0 * (x * y - z * w), s[1] * (1.0 - 2.0 * (x * x + z * z)), s[1] * 2.0 * (y * z + x * w), 0.0],
+                [s[2] * 2.0 * (x * z + y * w), s[2] * 2.0 * (y * z - x * w), s[2] * (1.0 - 2.0 * (x * x + y * y)), 0.0],
+                [t[0], t[1], t[2], 1.0],
+            ]
+        },
+    };
+    Node {
+        name: name(json, format!("node {}", i)),
+        mesh: index(json, "mesh"),
+        children: array(json, "children").iter().filter_map(|c| c.as_u64()).map(|c| c as usize).collect(),
+        matrix: matrix,
+    }
+}
+
+/// Parses a document, `base` being the directory external files are relative to.
+pub fn parse(text: &str, base: Option<&Path>) -> Result<Document, String> {
+    let json = try!(Json::from_str(text).map_err(|e| format!("{}", e)));
+    let version = json.find_path(&["asset", "version"]).and_then(|v| v.as_string()).unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("glTF version '{}' is not supported", version));
+    }
+
+    let mut buffers = Vec::new();
+    for buffer in array(&json, "buffers") {
+        let uri = try!(buffer.find("uri").and_then(|u| u.as_string()).ok_or("buffers without an uri are not supported".to_string()));
+        buffers.push(try!(read_uri(uri, base)));
+    }
+    let loader = Loader { json: &json, buffers: buffers };
+
+    let mut images = Vec::new();
+    for (i, img) in array(&json, "images").iter().enumerate() {
+        images.push(try!(loader.image(img, base).map_err(|e| format!("image {}: {}", i, e))));
+    }
+    // textures point at images, the sampler settings are ignored
+    let textures: Vec<Option<usize>> = array(&json, "textures").iter().map(|t| index(t, "source")).collect();
+
+    let materials = array(&json, "materials").iter().enumerate().map(|(i, m)| {
+        let pbr = m.find("pbrMetallicRoughness");
+        let factor = pbr.map_or(vec![1.0; 4], |p| numbers_or(p, "baseColorFactor", &[1.0; 4]));
+        Material {
+            name: name(m, format!("material {}", i)),
+            base_color: [factor[0], factor[1], factor[2], factor[3]],
+            base_color_texture: pbr.and_then(|p| p.find_path(&["baseColorTexture", "index"]))
+                .and_then(|t| t.as_u64())
+                .and_then(|t| textures.get(t as usize).cloned().unwrap_or(None)),
+            metallic: pbr.map_or(1.0, |p| number(p, "metallicFactor", 1.0)),
+            roughness: pbr.map_or(1.0, |p| number(p, "roughnessFactor", 1.0)),
+        }
+    }).collect();
+
+    let mut meshes = Vec::new();
+    for (i, mesh) in array(&json, "meshes").iter().enumerate() {
+        meshes.push(try!(loader.mesh(i, mesh)));
+    }
+
+    let nodes: Vec<Node> = array(&json, "nodes").iter().enumerate().map(|(i, n)| node(i, n)).collect();
+    if nodes.iter().any(|n| n.children.iter().any(|&c| c >= nodes.len()) || n.mesh.map_or(false, |m| m >= meshes.len())) {
+        return Err("a node refers to a missing node or mesh".to_string());
+    }
+    let roots = match array(&json, "scenes").get(index(&json, "scene").unwrap_or(0)) {
+        Some(scene) => array(scene, "nodes").iter().filter_map(|n| n.as_u64()).map(|n| n as usize)
+            .filter(|&n| n < nodes.len()).collect(),
+        // no scene: every node without a parent
+        None => (0..nodes.len()).filter(|&i| !nodes.iter().any(|n| n.children.contains(&i))).collect(),
+    };
+
+    Ok(Document {
+        nodes: nodes,
+        roots: roots,
+        meshes: meshes,
+        materials: materials,
+        images: images,
+    })
+}
+
+pub fn load(path: &str) -> Result<Document, String> {
+    let mut text = String::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_string(&mut text)).map_err(|e| format!("{}", e)));
+    let base: Option<PathBuf> = Path::new(path).parent().map(|p| p.to_path_buf());
+    parse(&text, base.as_ref().map(|p| p.as_path()))
+}
@@ -0,0 +1,464 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// glTF 2.0 scene viewer.
+//
+// The scene given on the command line, or the bundled one, is loaded at startup: every
+// primitive gets its own vertex and index buffers and every image a texture. The node
+// hierarchy is walked each frame to draw the meshes with a metallic-roughness shader lit by
+// one directional light. The side panel shows the scene graph as a tree for selecting and
+// hiding nodes; hiding a node hides its children as well.
+
+#[macro_use]
+extern crate gfx;
+extern crate gfx_app;
+extern crate cgmath;
+extern crate byteorder;
+extern crate image;
+extern crate rustc_serialize;
+
+extern crate sciter;
+extern crate gfx_sciter_examples;
+
+mod gltf;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+pub use gfx::format::{Srgba8, DepthStencil};
+
+use gltf::Matrix;
+use gfx_sciter_examples::to_number;
+
+type SciterHost = Rc<sciter::Host>;
+
+struct View {
+    api: &'static sciter::ISciterAPI,
+    hwnd: sciter::types::HWINDOW,
+}
+
+gfx_vertex_struct!( Vertex {
+    pos: [f32; 3] = "a_Pos",
+    normal: [f32; 3] = "a_Normal",
+    uv: [f32; 2] = "a_Uv",
+});
+
+gfx_constant_struct!( Locals {
+    model: [[f32; 4]; 4] = "u_Model",
+    view_proj: [[f32; 4]; 4] = "u_ViewProj",
+    base_color: [f32; 4] = "u_BaseColor",
+    // metallic, roughness, highlight
+    material: [f32; 4] = "u_Material",
+    light_dir: [f32; 4] = "u_LightDir",
+    camera_pos: [f32; 4] = "u_CameraPos",
+});
+
+gfx_pipeline!( pipe {
+    vbuf: gfx::VertexBuffer<Vertex> = (),
+    locals: gfx::ConstantBuffer<Locals> = "Locals",
+    base_color: gfx::TextureSampler<[f32; 4]> = "t_BaseColor",
+    out: gfx::RenderTarget<Srgba8> = "Target0",
+    depth: gfx::DepthTarget<DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
+});
+
+struct Primitive<R: gfx::Resources> {
+    vbuf: gfx::handle::Buffer<R, Vertex>,
+    slice: gfx::Slice<R>,
+    // index into `App::materials`
+    material: usize,
+    triangles: usize,
+}
+
+struct Material<R: gfx::Resources> {
+    material: gltf::Material,
+    texture: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+}
+
+// orbit around the center of the scene, angles in degrees
+#[derive(Clone, Copy)]
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+    zoom: f32,
+}
+
+// the direction towards the sun
+const LIGHT_DIR: [f32; 3] = [0.4, 0.8, 0.45];
+
+// world transform of every node, `None` for the nodes outside of the scene
+fn world_matrices(doc_nodes: &[gltf::Node], roots: &[usize]) -> Vec<Option<Matrix>> {
+    let mut world = vec![None; doc_nodes.len()];
+    let mut stack: Vec<(usize, Matrix)> = roots.iter().map(|&r| (r, gltf::IDENTITY)).collect();
+    while let Some((node, parent)) = stack.pop() {
+        // the hierarchy is meant to be a forest, don't loop on broken documents
+        if world[node].is_some() {
+            continue;
+        }
+        let matrix = gltf::mul(&parent, &doc_nodes[node].matrix);
+        world[node] = Some(matrix);
+        stack.extend(doc_nodes[node].children.iter().map(|&c| (c, matrix)));
+    }
+    world
+}
+
+struct App<R: gfx::Resources>{
+    pso: gfx::PipelineState<R, pipe::Meta>,
+    data: pipe::Data<R>,
+    nodes: Rc<Vec<gltf::Node>>,
+    roots: Vec<usize>,
+    meshes: Rc<Vec<Vec<Primitive<R>>>>,
+    mesh_names: Vec<String>,
+    materials: Vec<Material<R>>,
+    center: [f32; 3],
+    radius: f32,
+    aspect_ratio: f32,
+    visible: Rc<RefCell<Vec<bool>>>,
+    selected: Rc<Cell<Option<usize>>>,
+    camera: Rc<Cell<Camera>>,
+    view: Option<View>,
+}
+
+impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
+    fn new<F: gfx::Factory<R>>(mut factory: F, init: gfx_app::Init<R>) -> Self {
+        use gfx::traits::FactoryExt;
+
+        let vs = gfx_app::shade::Source {
+            glsl_150: include_bytes!("shader/pbr_150.glslv"),
+            hlsl_40:  include_bytes!("data/vertex.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+        let ps = gfx_app::shade::Source {
+            glsl_150: include_bytes!("shader/pbr_150.glslf"),
+            hlsl_40:  include_bytes!("data/pixel.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+
+        // the scene from the command line, or the bundled one
+        let doc = match std::env::args().nth(1) {
+            Some(path) => gltf::load(&path).unwrap_or_else(|e| panic!("unable to load {}: {}", path, e)),
+            None => gltf::parse(include_str!("model/scene.gltf"), None).unwrap(),
+        };
+
+        let sampler = factory.create_sampler(gfx::tex::SamplerInfo::new(
+            gfx::tex::FilterMethod::Bilinear, gfx::tex::WrapMode::Tile));
+        let textures: Vec<_> = doc.images.iter().map(|img| {
+            let (width, height) = img.dimensions();
+            let kind = gfx::tex::Kind::D2(width as gfx::tex::Size, height as gfx::tex::Size, gfx::tex::AaMode::Single);
+            let data: &[u8] = img;
+            factory.create_texture_const_u8::<Srgba8>(kind, &[data]).unwrap().1
+        }).collect();
+        // for the materials without a texture
+        let white = {
+            let kind = gfx::tex::Kind::D2(1, 1, gfx::tex::AaMode::Single);
+            let data: &[u8] = &[0xFF; 4];
+            factory.create_texture_const_u8::<Srgba8>(kind, &[data]).unwrap().1
+        };
+
+        // the fallback material goes last
+        let materials: Vec<Material<R>> = doc.materials.iter().chain(Some(gltf::Material::fallback()).iter())
+            .map(|m| Material {
+                material: m.clone(),
+                texture: m.base_color_texture.and_then(|t| textures.get(t)).unwrap_or(&white).clone(),
+            }).collect();
+        let fallback = materials.len() - 1;
+
+        let meshes: Vec<Vec<Primitive<R>>> = doc.meshes.iter().map(|mesh| {
+            mesh.primitives.iter().map(|p| {
+                let (vbuf, slice) = factory.create_vertex_buffer_indexed(&p.vertices, &p.indices[..]);
+                Primitive {
+                    vbuf: vbuf,
+                    slice: slice,
+                    material: match p.material {
+                        Some(m) if m < fallback => m,
+                        _ => fallback,
+                    },
+                    triangles: p.indices.len() / 3,
+                }
+            }).collect()
+        }).collect();
+
+        // bounds of the scene, from the bounds of the primitives
+        let (mut min, mut max) = ([::std::f32::MAX; 3], [::std::f32::MIN; 3]);
+        for (node, world) in doc.nodes.iter().zip(world_matrices(&doc.nodes, &doc.roots)) {
+            if let (Some(mesh), Some(world)) = (node.mesh, world) {
+                for p in &doc.meshes[mesh].primitives {
+                    for corner in 0..8 {
+                        let local = [
+                            if corner & 1 == 0 { p.min[0] } else { p.max[0] },
+                            if corner & 2 == 0 { p.min[1] } else { p.max[1] },
+                            if corner & 4 == 0 { p.min[2] } else { p.max[2] },
+                        ];
+                        let pos = gltf::transform_point(&world, local);
+                        for i in 0..3 {
+                            min[i] = min[i].min(pos[i]);
+                            max[i] = max[i].max(pos[i]);
+                        }
+                    }
+                }
+            }
+        }
+        let (mut center, mut diagonal) = ([0.0; 3], 0.0);
+        if min[0] <= max[0] {
+            for i in 0..3 {
+                center[i] = 0.5 * (min[i] + max[i]);
+                diagonal += (max[i] - min[i]) * (max[i] - min[i]);
+            }
+        }
+
+        let pso = factory.create_pipeline_simple(
+            vs.select(init.backend).unwrap(),
+            ps.select(init.backend).unwrap(),
+            gfx::state::CullFace::Nothing,
+            pipe::new()
+        ).unwrap();
+
+        // placeholders, replaced for every primitive
+        let (vbuf, _) = factory.create_vertex_buffer(&[Vertex { pos: [0.0; 3], normal: [0.0; 3], uv: [0.0; 2] }]);
+        let data = pipe::Data {
+            vbuf: vbuf,
+            locals: factory.create_constant_buffer(1),
+            base_color: (white, sampler),
+            out: init.color,
+            depth: init.depth,
+        };
+
+        App {
+            pso: pso,
+            data: data,
+            visible: Rc::new(RefCell::new(vec![true; doc.nodes.len()])),
+            nodes: Rc::new(doc.nodes),
+            roots: doc.roots,
+            meshes: Rc::new(meshes),
+            mesh_names: doc.meshes.iter().map(|mesh| mesh.name.clone()).collect(),
+            materials: materials,
+            center: center,
+            radius: (0.5 * f32::sqrt(diagonal)).max(0.001),
+            aspect_ratio: init.aspect_ratio,
+            selected: Rc::new(Cell::new(None)),
+            camera: Rc::new(Cell::new(Camera { yaw: 30.0, pitch: 25.0, zoom: 1.0 })),
+            view: None,
+        }
+    }
+
+    fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
+        use cgmath::{deg, AffineMatrix3, Matrix4, Point3, Transform, Vector3};
+
+        let camera = self.camera.get();
+        let (yaw, pitch) = (camera.yaw.to_radians(), camera.pitch.to_radians());
+        let distance = 2.5 * self.radius / camera.zoom;
+        let c = self.center;
+        let eye = [
+            c[0] + distance * pitch.cos() * yaw.sin(),
+            c[1] + distance * pitch.sin(),
+            c[2] + distance * pitch.cos() * yaw.cos(),
+        ];
+        let view: AffineMatrix3<f32> = Transform::look_at(
+            Point3::new(eye[0], eye[1], eye[2]),
+            Point3::new(c[0], c[1], c[2]),
+            Vector3::unit_y(),
+        );
+        let near = (distance - self.radius).max(0.01 * self.radius);
+        let proj: Matrix4<f32> = cgmath::perspective(deg(45.0f32), self.aspect_ratio, near, distance + self.radius);
+        let view_proj: Matrix = (proj * view.mat).into();
+
+        encoder.clear(&self.data.out, [0.3, 0.3, 0.3, 1.0]);
+        encoder.clear_depth(&self.data.depth, 1.0);
+
+        // walk the visible part of the hierarchy, a node is highlighted with its children
+        let visible = self.visible.borrow();
+        let selected = self.selected.get();
+        let mut stack: Vec<(usize, Matrix, bool)> = self.roots.iter().map(|&r| (r, gltf::IDENTITY, false)).collect();
+        let mut drawn = vec![false; self.nodes.len()];
+        while let Some((index, parent, highlight)) = stack.pop() {
+            if !visible[index] || drawn[index] {
+                continue;
+            }
+            drawn[index] = true;
+            let node = &self.nodes[index];
+            let world = gltf::mul(&parent, &node.matrix);
+            let highlight = highlight || selected == Some(index);
+            stack.extend(node.children.iter().map(|&c| (c, world, highlight)));
+
+            let mesh = match node.mesh {
+                Some(mesh) => &self.meshes[mesh],
+                None => continue,
+            };
+            for primitive in mesh {
+                let material = &self.materials[primitive.material];
+                let m = &material.material;
+                encoder.update_constant_buffer(&self.data.locals, &Locals {
+                    model: world,
+                    view_proj: view_proj,
+                    base_color: m.base_color,
+                    material: [m.metallic, m.roughness, if highlight { 1.0 } else { 0.0 }, 0.0],
+                    light_dir: [LIGHT_DIR[0], LIGHT_DIR[1], LIGHT_DIR[2], 0.0],
+                    camera_pos: [eye[0], eye[1], eye[2], 1.0],
+                });
+                self.data.vbuf = primitive.vbuf.clone();
+                self.data.base_color.0 = material.texture.clone();
+                encoder.draw(&primitive.slice, &self.pso, &self.data);
+            }
+        }
+    }
+
+    fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
+      self.render_document();
+      return true;
+    }
+
+    fn setup<WindowHost: Any>(&mut self, host: &WindowHost) {
+
+      let any = host as &Any;
+      if !any.is::<SciterHost>() {
+        return;
+      }
+      let host = any.downcast_ref::<SciterHost>().unwrap();
+
+      // load UI from html
+      let ui = include_bytes!("facade.htm");
+      host.load_html(ui, None);
+
+      // attach root handler
+      if let Some(root) = host.get_root() {
+        println!("document loaded: {}", root);
+
+        let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
+        self.view = Some(View { api: api, hwnd: host.get_hwnd() });
+
+        let mesh_names = self.mesh_names.iter().cloned();
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          nodes: self.nodes.clone(),
+          meshes: self.meshes.iter().zip(mesh_names).map(|(mesh, name)| {
+            (name, mesh.iter().fold(0, |sum, p| sum + p.triangles))
+          }).collect(),
+          materials: self.materials.iter().map(|m| m.material.name.clone()).collect(),
+          primitive_materials: self.meshes.iter().map(|mesh| mesh.iter().map(|p| p.material).collect()).collect(),
+          visible: self.visible.clone(),
+          selected: self.selected.clone(),
+          camera: self.camera.clone(),
+        };
+        host.attach_handler(handler);
+
+        let tree: sciter::Value = self.roots.iter().map(|&r| node_to_value(&self.nodes, r, 0)).collect();
+        root.call_function("setupScene", &[tree]).ok();
+
+      } else {
+        println!("oops: no root element!");
+      }
+    }
+}
+
+
+
+impl<R: gfx::Resources> App<R> {
+
+  fn render_document(&mut self) {
+    if self.view.is_none() {
+      return;
+    }
+    use sciter::types::BOOL;
+    let view = self.view.as_ref().unwrap();
+    let el = 0 as sciter::HELEMENT;
+    (view.api.SciterRenderOnDirectXWindow)(view.hwnd, el, false as BOOL);
+  }
+}
+
+// deeper nodes are left out of the tree view
+const MAX_TREE_DEPTH: usize = 64;
+
+// `[index, name, [children]]`
+fn node_to_value(nodes: &[gltf::Node], index: usize, depth: usize) -> sciter::Value {
+  let node = &nodes[index];
+  let children: sciter::Value = if depth < MAX_TREE_DEPTH {
+    node.children.iter().map(|&c| node_to_value(nodes, c, depth + 1)).collect()
+  } else {
+    Vec::<sciter::Value>::new().into_iter().collect()
+  };
+  vec![
+    sciter::Value::from(index as i32),
+    sciter::Value::from(&node.name[..]),
+    children,
+  ].into_iter().collect()
+}
+
+#[allow(dead_code)]
+struct Handler {
+  host: Weak<sciter::Host>,
+  nodes: Rc<Vec<gltf::Node>>,
+  // name and triangle count of every mesh
+  meshes: Vec<(String, usize)>,
+  materials: Vec<String>,
+  // material of every primitive of every mesh
+  primitive_materials: Vec<Vec<usize>>,
+  visible: Rc<RefCell<Vec<bool>>>,
+  selected: Rc<Cell<Option<usize>>>,
+  camera: Rc<Cell<Camera>>,
+}
+
+impl sciter::EventHandler for Handler {
+
+  #[allow(unused_variables)]
+  fn on_script_call(&mut self, root: sciter::HELEMENT, name: &str, args: &[sciter::Value]) -> Option<sciter::Value> {
+    let ok = sciter::Value::from(true);
+    match name {
+      "setNodeVisible" => {
+        let index = args[0].to_int().unwrap() as usize;
+        if let Some(visible) = self.visible.borrow_mut().get_mut(index) {
+          *visible = args[1].to_bool().unwrap();
+        }
+        Some(ok)
+      },
+      "selectNode" => {
+        // `[name, mesh name, triangles, [material names]]` of the selected node, or false
+        let index = args[0].to_int().unwrap();
+        let node = if index >= 0 { self.nodes.get(index as usize) } else { None };
+        self.selected.set(node.map(|_| index as usize));
+        Some(match node {
+          Some(node) => {
+            let (mesh_name, triangles, materials): (&str, usize, sciter::Value) = match node.mesh {
+              Some(mesh) => {
+                let materials = self.primitive_materials[mesh].iter().map(|&m| &self.materials[m][..]).collect();
+                (&self.meshes[mesh].0, self.meshes[mesh].1, materials)
+              },
+              None => ("", 0, Vec::<&str>::new().into_iter().collect()),
+            };
+            vec![
+              sciter::Value::from(&node.name[..]),
+              sciter::Value::from(mesh_name),
+              sciter::Value::from(triangles as i32),
+              materials,
+            ].into_iter().collect()
+          },
+          None => sciter::Value::from(false),
+        })
+      },
+      "setCamera" => {
+        self.camera.set(Camera {
+          yaw: to_number(&args[0]).unwrap() as f32,
+          pitch: (to_number(&args[1]).unwrap() as f32).max(-89.0).min(89.0),
+          zoom: (to_number(&args[2]).unwrap() as f32).max(0.1),
+        });
+        Some(ok)
+      },
+      _ => None,
+    }
+  }
+}
+
+pub fn main() {
+    use gfx_app::Application;
+    App::launch_default("glTF viewer example");
+}
@@ -0,0 +1,67 @@
+struct VsOutput {
+	float4 pos: SV_Position;
+	float3 world: POSITION;
+	float3 normal: NORMAL;
+	float2 uv: TEXCOORD;
+};
+
+cbuffer Locals {
+	float4x4 u_Model;
+	float4x4 u_ViewProj;
+	float4 u_BaseColor;
+	// metallic, roughness, highlight
+	float4 u_Material;
+	float4 u_LightDir;
+	float4 u_CameraPos;
+};
+
+VsOutput Vertex(float3 pos: a_Pos, float3 normal: a_Normal, float2 uv: a_Uv) {
+	float4 world = mul(u_Model, float4(pos, 1.0));
+	// exact for rotations and uniform scales
+	float3 n = mul((float3x3)u_Model, normal);
+	VsOutput output = { mul(u_ViewProj, world), world.xyz, n, uv };
+	return output;
+}
+
+Texture2D<float4> t_BaseColor;
+SamplerState t_BaseColor_;
+
+static const float PI = 3.14159265;
+static const float LIGHT_INTENSITY = 3.0;
+static const float AMBIENT = 0.15;
+static const float3 HIGHLIGHT = float3(1.0, 0.6, 0.1);
+
+float4 Pixel(VsOutput pin): SV_Target {
+	float4 base = u_BaseColor * t_BaseColor.Sample(t_BaseColor_, pin.uv);
+	float metallic = u_Material.x;
+	float roughness = max(u_Material.y, 0.04);
+
+	float3 n = normalize(pin.normal);
+	float3 v = normalize(u_CameraPos.xyz - pin.world);
+	// the back faces are lit as front ones
+	if (dot(n, v) < 0.0)
+		n = -n;
+	float3 l = normalize(u_LightDir.xyz);
+	float3 h = normalize(l + v);
+	float nl = max(dot(n, l), 0.0);
+	float nv = max(dot(n, v), 0.0001);
+	float nh = max(dot(n, h), 0.0);
+	float vh = max(dot(v, h), 0.0);
+
+	// GGX distribution, Smith-Schlick visibility and Schlick fresnel
+	float a = roughness * roughness;
+	float a2 = a * a;
+	float dn = nh * nh * (a2 - 1.0) + 1.0;
+	float d = a2 / (PI * dn * dn);
+	float k = 0.5 * a;
+	float g = nl / (nl * (1.0 - k) + k) * nv / (nv * (1.0 - k) + k);
+	float3 f0 = lerp(float3(0.04, 0.04, 0.04), base.rgb, metallic);
+	float3 f = f0 + (1.0 - f0) * pow(1.0 - vh, 5.0);
+
+	float3 specular = d * g * f / max(4.0 * nl * nv, 0.0001);
+	float3 diffuse = (1.0 - f) * (1.0 - metallic) * base.rgb / PI;
+	float3 ambient = AMBIENT * lerp(base.rgb, f0, metallic);
+	float3 color = (diffuse + specular) * nl * LIGHT_INTENSITY + ambient;
+
+	return float4(lerp(color, HIGHLIGHT, 0.35 * u_Material.z), base.a);
+}
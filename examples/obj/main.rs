@@ -0,0 +1,379 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Wavefront OBJ viewer.
+//
+// The model is given on the command line or opened from the side panel, which also shows its
+// statistics and toggles the visibility of its groups. Each group is a range of the index
+// buffer and is drawn with its own slice.
+
+#[macro_use]
+extern crate gfx;
+extern crate gfx_app;
+extern crate cgmath;
+
+extern crate sciter;
+extern crate gfx_sciter_examples;
+
+mod obj;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+pub use gfx::format::{Srgba8, DepthStencil};
+use gfx_sciter_examples::{path_from_url, to_number};
+
+type SciterHost = Rc<sciter::Host>;
+
+struct View {
+    api: &'static sciter::ISciterAPI,
+    hwnd: sciter::types::HWINDOW,
+}
+
+// Models opened while running are uploaded after the factory is gone, so the buffers
+// for them are created up front with room for this many elements. The model given on the
+// command line gets buffers of its own size.
+const MAX_VERTICES: usize = 1 << 16;
+const MAX_INDICES: usize = 3 << 16;
+
+gfx_vertex_struct!( Vertex {
+    pos: [f32; 3] = "a_Pos",
+    normal: [f32; 3] = "a_Normal",
+});
+
+gfx_constant_struct!( Locals {
+    transform: [[f32; 4]; 4] = "u_Transform",
+    light_dir: [f32; 4] = "u_LightDir",
+    color: [f32; 4] = "u_Color",
+});
+
+gfx_pipeline!( pipe {
+    vbuf: gfx::VertexBuffer<Vertex> = (),
+    locals: gfx::ConstantBuffer<Locals> = "Locals",
+    out: gfx::RenderTarget<Srgba8> = "Target0",
+    depth: gfx::DepthTarget<DepthStencil> = gfx::preset::depth::LESS_EQUAL_WRITE,
+});
+
+// what the side panel shows about the model
+struct ModelInfo {
+    name: String,
+    vertices: usize,
+    faces: usize,
+    triangles: usize,
+    min: [f32; 3],
+    max: [f32; 3],
+    groups: Vec<String>,
+}
+
+impl ModelInfo {
+    fn new(name: &str, mesh: &obj::Mesh) -> ModelInfo {
+        ModelInfo {
+            name: name.to_string(),
+            vertices: mesh.vertices.len(),
+            faces: mesh.faces,
+            triangles: mesh.indices.len() / 3,
+            min: mesh.min,
+            max: mesh.max,
+            groups: mesh.groups.iter().map(|g| g.name.clone()).collect(),
+        }
+    }
+}
+
+// orbit around the center of the model, angles in degrees
+#[derive(Clone, Copy)]
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+    zoom: f32,
+}
+
+fn load_model(path: &str) -> Result<obj::Mesh, String> {
+    let mesh = try!(obj::load(path));
+    if mesh.vertices.len() > MAX_VERTICES || mesh.indices.len() > MAX_INDICES {
+        return Err(format!("{} vertices and {} indices, at most {} and {} are supported",
+                           mesh.vertices.len(), mesh.indices.len(), MAX_VERTICES, MAX_INDICES));
+    }
+    Ok(mesh)
+}
+
+struct App<R: gfx::Resources>{
+    pso: gfx::PipelineState<R, pipe::Meta>,
+    data: pipe::Data<R>,
+    // the buffers models opened from the side panel are uploaded to
+    opened: (gfx::handle::Buffer<R, Vertex>, gfx::handle::Buffer<R, u32>),
+    // one slice per group of the current model
+    slices: Vec<gfx::Slice<R>>,
+    center: [f32; 3],
+    radius: f32,
+    aspect_ratio: f32,
+    // a model waiting to be uploaded
+    pending: Rc<RefCell<Option<obj::Mesh>>>,
+    info: Rc<RefCell<ModelInfo>>,
+    visible: Rc<RefCell<Vec<bool>>>,
+    camera: Rc<Cell<Camera>>,
+    view: Option<View>,
+}
+
+impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
+    fn new<F: gfx::Factory<R>>(mut factory: F, init: gfx_app::Init<R>) -> Self {
+        use gfx::traits::FactoryExt;
+
+        let vs = gfx_app::shade::Source {
+            glsl_150: include_bytes!("shader/mesh_150.glslv"),
+            hlsl_40:  include_bytes!("data/vertex.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+        let ps = gfx_app::shade::Source {
+            glsl_150: include_bytes!("shader/mesh_150.glslf"),
+            hlsl_40:  include_bytes!("data/pixel.fx"),
+            .. gfx_app::shade::Source::empty()
+        };
+
+        // the model from the command line, or the bundled one when there is none or it fails to load
+        let loaded = std::env::args().nth(1).and_then(|path| match obj::load(&path) {
+            Ok(mesh) => Some((path, mesh)),
+            Err(e) => {
+                println!("unable to load {}: {}", path, e);
+                None
+            },
+        });
+        let (name, mesh) = loaded.unwrap_or_else(|| {
+            ("house.obj".to_string(), obj::parse(include_str!("model/house.obj")).unwrap())
+        });
+
+        let (vbuf, slice) = factory.create_vertex_buffer_indexed(&mesh.vertices, &mesh.indices[..]);
+        let opened = (
+            factory.create_buffer_dynamic(MAX_VERTICES, gfx::BufferRole::Vertex, gfx::Bind::empty()).unwrap(),
+            factory.create_buffer_dynamic(MAX_INDICES, gfx::BufferRole::Index, gfx::Bind::empty()).unwrap(),
+        );
+
+        let pso = factory.create_pipeline_simple(
+            vs.select(init.backend).unwrap(),
+            ps.select(init.backend).unwrap(),
+            gfx::state::CullFace::Nothing,
+            pipe::new()
+        ).unwrap();
+
+        let data = pipe::Data {
+            vbuf: vbuf,
+            locals: factory.create_constant_buffer(1),
+            out: init.color,
+            depth: init.depth,
+        };
+
+        let mut app = App {
+            pso: pso,
+            data: data,
+            opened: opened,
+            slices: Vec::new(),
+            center: [0.0; 3],
+            radius: 1.0,
+            aspect_ratio: init.aspect_ratio,
+            info: Rc::new(RefCell::new(ModelInfo::new(&name, &mesh))),
+            visible: Rc::new(RefCell::new(vec![true; mesh.groups.len()])),
+            pending: Rc::new(RefCell::new(None)),
+            camera: Rc::new(Cell::new(Camera { yaw: 30.0, pitch: 20.0, zoom: 1.0 })),
+            view: None,
+        };
+        app.show_mesh(&mesh, slice.buffer);
+        app
+    }
+
+    fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
+        let pending = self.pending.borrow_mut().take();
+        if let Some(mesh) = pending {
+            let (vbuf, ibuf) = self.opened.clone();
+            encoder.update_buffer(&vbuf, &mesh.vertices, 0).unwrap();
+            encoder.update_buffer(&ibuf, &mesh.indices, 0).unwrap();
+            self.data.vbuf = vbuf;
+            self.show_mesh(&mesh, gfx::IndexBuffer::Index32(ibuf));
+        }
+
+        {
+            use cgmath::{deg, AffineMatrix3, Matrix4, Point3, Transform, Vector3};
+            let camera = self.camera.get();
+            let (yaw, pitch) = (camera.yaw.to_radians(), camera.pitch.to_radians());
+            let dir = [pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos()];
+            let distance = 2.5 * self.radius / camera.zoom;
+            let c = self.center;
+            let view: AffineMatrix3<f32> = Transform::look_at(
+                Point3::new(c[0] + distance * dir[0], c[1] + distance * dir[1], c[2] + distance * dir[2]),
+                Point3::new(c[0], c[1], c[2]),
+                Vector3::unit_y(),
+            );
+            let near = (distance - self.radius).max(0.01 * self.radius);
+            let proj: Matrix4<f32> = cgmath::perspective(deg(45.0f32), self.aspect_ratio, near, distance + self.radius);
+            // a headlight, so the visible side is always lit
+            let locals = Locals {
+                transform: (proj * view.mat).into(),
+                light_dir: [dir[0], dir[1], dir[2], 0.0],
+                color: [0.8, 0.75, 0.7, 1.0],
+            };
+            encoder.update_constant_buffer(&self.data.locals, &locals);
+        }
+
+        encoder.clear(&self.data.out, [0.3, 0.3, 0.3, 1.0]);
+        encoder.clear_depth(&self.data.depth, 1.0);
+        let visible = self.visible.borrow();
+        for (i, slice) in self.slices.iter().enumerate() {
+            if visible.get(i).cloned().unwrap_or(true) {
+                encoder.draw(slice, &self.pso, &self.data);
+            }
+        }
+    }
+
+    fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
+      self.render_document();
+      return true;
+    }
+
+    fn setup<WindowHost: Any>(&mut self, host: &WindowHost) {
+
+      let any = host as &Any;
+      if !any.is::<SciterHost>() {
+        return;
+      }
+      let host = any.downcast_ref::<SciterHost>().unwrap();
+
+      // load UI from html
+      let ui = include_bytes!("facade.htm");
+      host.load_html(ui, None);
+
+      // attach root handler
+      if let Some(root) = host.get_root() {
+        println!("document loaded: {}", root);
+
+        let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
+        self.view = Some(View { api: api, hwnd: host.get_hwnd() });
+
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          pending: self.pending.clone(),
+          info: self.info.clone(),
+          visible: self.visible.clone(),
+          camera: self.camera.clone(),
+        };
+        host.attach_handler(handler);
+
+        root.call_function("setupModel", &[info_to_value(&self.info.borrow())]).ok();
+
+      } else {
+        println!("oops: no root element!");
+      }
+    }
+}
+
+
+
+impl<R: gfx::Resources> App<R> {
+
+  // one slice per group into `index`, and the camera target
+  fn show_mesh(&mut self, mesh: &obj::Mesh, index: gfx::IndexBuffer<R>) {
+    self.slices = mesh.groups.iter().map(|group| gfx::Slice {
+      start: group.start,
+      end: group.end,
+      base_vertex: 0,
+      instances: None,
+      buffer: index.clone(),
+    }).collect();
+    let mut diagonal = 0.0;
+    for i in 0..3 {
+      self.center[i] = 0.5 * (mesh.min[i] + mesh.max[i]);
+      diagonal += (mesh.max[i] - mesh.min[i]) * (mesh.max[i] - mesh.min[i]);
+    }
+    self.radius = (0.5 * f32::sqrt(diagonal)).max(0.001);
+  }
+
+  fn render_document(&mut self) {
+    if self.view.is_none() {
+      return;
+    }
+    use sciter::types::BOOL;
+    let view = self.view.as_ref().unwrap();
+    let el = 0 as sciter::HELEMENT;
+    (view.api.SciterRenderOnDirectXWindow)(view.hwnd, el, false as BOOL);
+  }
+}
+
+#[allow(dead_code)]
+struct Handler {
+  host: Weak<sciter::Host>,
+  pending: Rc<RefCell<Option<obj::Mesh>>>,
+  info: Rc<RefCell<ModelInfo>>,
+  visible: Rc<RefCell<Vec<bool>>>,
+  camera: Rc<Cell<Camera>>,
+}
+
+// `[name, vertices, faces, triangles, [min], [max], [group names]]`
+fn info_to_value(info: &ModelInfo) -> sciter::Value {
+  vec![
+    sciter::Value::from(&info.name[..]),
+    sciter::Value::from(info.vertices as i32),
+    sciter::Value::from(info.faces as i32),
+    sciter::Value::from(info.triangles as i32),
+    info.min.iter().map(|&x| x as f64).collect(),
+    info.max.iter().map(|&x| x as f64).collect(),
+    info.groups.iter().map(|name| &name[..]).collect(),
+  ].into_iter().collect()
+}
+
+impl sciter::EventHandler for Handler {
+
+  #[allow(unused_variables)]
+  fn on_script_call(&mut self, root: sciter::HELEMENT, name: &str, args: &[sciter::Value]) -> Option<sciter::Value> {
+    let ok = sciter::Value::from(true);
+    match name {
+      "loadModel" => {
+        let path = args[0].as_string().unwrap();
+        let path = path_from_url(&path);
+        let path: &str = &path;
+        match load_model(path) {
+          Ok(mesh) => {
+            println!("loaded {}: {} vertices, {} faces", path, mesh.vertices.len(), mesh.faces);
+            *self.info.borrow_mut() = ModelInfo::new(path, &mesh);
+            *self.visible.borrow_mut() = vec![true; mesh.groups.len()];
+            *self.pending.borrow_mut() = Some(mesh);
+            Some(info_to_value(&self.info.borrow()))
+          },
+          Err(e) => {
+            println!("unable to load {}: {}", path, e);
+            Some(sciter::Value::from(false))
+          }
+        }
+      },
+      "setGroupVisible" => {
+        let index = args[0].to_int().unwrap() as usize;
+        if let Some(visible) = self.visible.borrow_mut().get_mut(index) {
+          *visible = args[1].to_bool().unwrap();
+        }
+        Some(ok)
+      },
+      "setCamera" => {
+        self.camera.set(Camera {
+          yaw: to_number(&args[0]).unwrap() as f32,
+          pitch: (to_number(&args[1]).unwrap() as f32).max(-89.0).min(89.0),
+          zoom: (to_number(&args[2]).unwrap() as f32).max(0.1),
+        });
+        Some(ok)
+      },
+      _ => None,
+    }
+  }
+}
+
+pub fn main() {
+    use gfx_app::Application;
+    App::launch_default("OBJ viewer example");
+}
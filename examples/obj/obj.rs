@@ -0,0 +1,193 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Wavefront OBJ loading.
+//
+// Only the geometry is read: `v`, `vn` and `f`, with `g` and `o` starting a new group.
+// Texture coordinates, materials and free-form geometry are ignored. Polygons are
+// triangulated as fans. When a face has no normals, the vertex normals are averaged
+// from the faces around each position.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+use Vertex;
+
+/// A named range of the index data.
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub groups: Vec<Group>,
+    /// Number of polygons in the file, before triangulation.
+    pub faces: usize,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len > 0.0 { [v[0] / len, v[1] / len, v[2] / len] } else { [0.0, 0.0, 1.0] }
+}
+
+fn parse_floats(words: &[&str], line: usize) -> Result<[f32; 3], String> {
+    if words.len() < 3 {
+        return Err(format!("line {}: expected 3 coordinates", line));
+    }
+    let mut out = [0.0; 3];
+    for i in 0..3 {
+        out[i] = try!(words[i].parse().map_err(|_| format!("line {}: bad number '{}'", line, words[i])));
+    }
+    Ok(out)
+}
+
+// OBJ indices are 1-based, negative ones count back from the last element
+fn parse_index(word: &str, count: usize, line: usize) -> Result<usize, String> {
+    let index: i64 = try!(word.parse().map_err(|_| format!("line {}: bad index '{}'", line, word)));
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("line {}: index {} out of range", line, index));
+    }
+    Ok(resolved as usize)
+}
+
+pub fn parse(text: &str) -> Result<Mesh, String> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    // (position, normal) pairs already emitted as a vertex
+    let mut emitted: HashMap<(usize, Option<usize>), u32> = HashMap::new();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices = Vec::new();
+    let mut groups = Vec::new();
+    let mut group_name = "default".to_string();
+    let mut group_start = 0;
+    let mut faces = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        match words[0] {
+            "v" => positions.push(try!(parse_floats(&words[1..], number))),
+            "vn" => normals.push(normalize(try!(parse_floats(&words[1..], number)))),
+            "g" | "o" => {
+                if indices.len() > group_start {
+                    groups.push(Group { name: group_name, start: group_start as u32, end: indices.len() as u32 });
+                }
+                group_name = if words.len() > 1 { words[1..].join(" ") } else { "unnamed".to_string() };
+                group_start = indices.len();
+            },
+            "f" => {
+                if words.len() < 4 {
+                    return Err(format!("line {}: a face needs at least 3 vertices", number));
+                }
+                let mut corners = Vec::with_capacity(words.len() - 1);
+                for word in &words[1..] {
+                    // v, v/vt, v//vn or v/vt/vn
+                    let parts: Vec<&str> = word.split('/').collect();
+                    let position = try!(parse_index(parts[0], positions.len(), number));
+                    let normal = match parts.get(2) {
+                        Some(n) if !n.is_empty() => Some(try!(parse_index(n, normals.len(), number))),
+                        _ => None,
+                    };
+                    let next = vertices.len() as u32;
+                    let index = *emitted.entry((position, normal)).or_insert(next);
+                    if index == next {
+                        vertices.push(Vertex {
+                            pos: positions[position],
+                            normal: normal.map_or([0.0; 3], |n| normals[n]),
+                        });
+                    }
+                    corners.push(index);
+                }
+                for i in 1 .. corners.len() - 1 {
+                    indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+                faces += 1;
+            },
+            _ => (),
+        }
+    }
+    if indices.len() > group_start {
+        groups.push(Group { name: group_name, start: group_start as u32, end: indices.len() as u32 });
+    }
+    if indices.is_empty() {
+        return Err("no faces found".to_string());
+    }
+
+    // area weighted face normals for the vertices that came without one
+    let smooth: Vec<bool> = {
+        let mut smooth = vec![false; vertices.len()];
+        for (&(_, normal), &index) in emitted.iter() {
+            smooth[index as usize] = normal.is_none();
+        }
+        smooth
+    };
+    for tri in indices.chunks(3) {
+        let (a, b, c) = (vertices[tri[0] as usize].pos, vertices[tri[1] as usize].pos, vertices[tri[2] as usize].pos);
+        let n = cross(sub(b, a), sub(c, a));
+        for &i in tri {
+            if smooth[i as usize] {
+                let v = &mut vertices[i as usize];
+                v.normal = [v.normal[0] + n[0], v.normal[1] + n[1], v.normal[2] + n[2]];
+            }
+        }
+    }
+    for (v, &smooth) in vertices.iter_mut().zip(smooth.iter()) {
+        if smooth {
+            v.normal = normalize(v.normal);
+        }
+    }
+
+    let (mut min, mut max) = ([::std::f32::MAX; 3], [::std::f32::MIN; 3]);
+    for v in &vertices {
+        for i in 0..3 {
+            min[i] = min[i].min(v.pos[i]);
+            max[i] = max[i].max(v.pos[i]);
+        }
+    }
+
+    Ok(Mesh {
+        vertices: vertices,
+        indices: indices,
+        groups: groups,
+        faces: faces,
+        min: min,
+        max: max,
+    })
+}
+
+pub fn load(path: &str) -> Result<Mesh, String> {
+    let mut text = String::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_string(&mut text)).map_err(|e| format!("{}", e)));
+    parse(&text)
+}
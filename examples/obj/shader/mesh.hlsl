@@ -0,0 +1,21 @@
+struct VsOutput {
+	float4 pos: SV_Position;
+	float3 normal: NORMAL;
+};
+
+cbuffer Locals {
+	float4x4 u_Transform;
+	float4 u_LightDir;
+	float4 u_Color;
+};
+
+VsOutput Vertex(float3 pos: a_Pos, float3 normal: a_Normal) {
+	VsOutput output = { mul(u_Transform, float4(pos, 1.0)), normal };
+	return output;
+}
+
+float4 Pixel(VsOutput pin): SV_Target {
+	// two-sided lambert with some ambient, OBJ winding is not always consistent
+	float diffuse = abs(dot(normalize(pin.normal), u_LightDir.xyz));
+	return float4(u_Color.rgb * (0.2 + 0.8 * diffuse), 1.0);
+}
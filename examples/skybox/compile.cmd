@@ -3,3 +3,5 @@ set FXC="%DXSDK_DIR%\Utilities\bin\x64\fxc.exe" -nologo
 if not exist data mkdir data
 %FXC% /T vs_4_0 /E Vertex /Fo data/vertex.fx shader/cubemap.hlsl
 %FXC% /T ps_4_0 /E Pixel /Fo data/pixel.fx shader/cubemap.hlsl
+%FXC% /T vs_4_0 /E Vertex /Fo data/panel_vs.fx shader/panel.hlsl
+%FXC% /T ps_4_0 /E Pixel /Fo data/panel_ps.fx shader/panel.hlsl
@@ -0,0 +1,46 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Sciter renders elements into D3D11 textures, which only Windows has. Elsewhere the panel
+// texture can't be created, so the panel isn't drawn and there is no input to forward to it.
+
+use gfx;
+use sciter;
+
+pub enum ElementTexture {}
+
+impl ElementTexture {
+    pub fn new<R, T>(_: &gfx::handle::Texture<R, T>) -> Option<ElementTexture>
+        where R: gfx::Resources
+    {
+        None
+    }
+
+    pub fn render(&self, _: &sciter::ISciterAPI, _: sciter::types::HWINDOW, _: &sciter::Element) -> bool {
+        match *self {}
+    }
+}
+
+pub enum MouseEvent {}
+
+impl MouseEvent {
+    pub fn from_name(_: &str) -> Option<MouseEvent> {
+        None
+    }
+}
+
+pub fn send_mouse(_: &sciter::ISciterAPI, _: sciter::types::HWINDOW, _: u32,
+                  event: MouseEvent, _: [i32; 2], _: [i32; 2], _: bool) -> bool {
+    match event {}
+}
@@ -22,9 +22,14 @@ extern crate cgmath;
 extern crate image;
 
 extern crate sciter;
+#[macro_use]
+extern crate gfx_sciter_examples;
+
+#[cfg(not(windows))]
+mod fallback;
 
 use std::any::Any;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::{Rc, Weak};
 
 type SciterHost = Rc<sciter::Host>;
@@ -32,11 +37,22 @@ type SciterHost = Rc<sciter::Host>;
 struct View {
     api: &'static sciter::ISciterAPI,
     hwnd: sciter::types::HWINDOW,
+    root: sciter::Element,
+    foreground: sciter::Element,
+    // rendered onto the floating panel
+    panel: sciter::Element,
 }
 
 
 
 use std::io::Cursor;
+use gfx_sciter_examples::inspect::{self, Inspect, Property};
+use gfx_sciter_examples::constants::Constants;
+#[cfg(windows)]
+use gfx_sciter_examples::offscreen::{self, ElementTexture, MouseEvent};
+#[cfg(not(windows))]
+use fallback::{self as offscreen, ElementTexture, MouseEvent};
+use gfx_sciter_examples::viewport::{self, Viewport};
 pub use gfx::format::{Srgba8, Depth, Rgba8};
 
 gfx_vertex_struct!( Vertex {
@@ -51,7 +67,7 @@ impl Vertex {
     }
 }
 
-gfx_constant_struct!( Locals {
+inspect_constant_struct!( Locals {
     inv_proj: [[f32; 4]; 4] = "u_InvProj",
     view: [[f32; 4]; 4] = "u_WorldToCamera",
 });
@@ -61,8 +77,71 @@ gfx_pipeline!( pipe {
     cubemap: gfx::TextureSampler<[f32; 4]> = "t_Cubemap",
     locals: gfx::ConstantBuffer<Locals> = "Locals",
     out: gfx::RenderTarget<Srgba8> = "Target0",
+    scissor: gfx::Scissor = (),
+});
+
+// The floating panel, a quad at the center of the scene showing `section#world-panel`.
+// Its texture is as big as the element.
+const PANEL_SIZE: u16 = 256;
+// half of the quad side, in world units
+const PANEL_EXTENT: f32 = 0.4;
+
+gfx_vertex_struct!( PanelVertex {
+    pos: [f32; 3] = "a_Pos",
+    uv: [f32; 2] = "a_Uv",
+});
+
+gfx_constant_struct!( PanelLocals {
+    transform: [[f32; 4]; 4] = "u_Transform",
 });
 
+// Direct2D writes premultiplied alpha
+const PREMULTIPLIED_ALPHA: gfx::state::Blend = gfx::state::Blend {
+    color: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::One,
+        destination: gfx::state::Factor::OneMinus(gfx::state::BlendValue::SourceAlpha),
+    },
+    alpha: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::One,
+        destination: gfx::state::Factor::OneMinus(gfx::state::BlendValue::SourceAlpha),
+    },
+};
+
+gfx_pipeline!( panel {
+    vbuf: gfx::VertexBuffer<PanelVertex> = (),
+    locals: gfx::ConstantBuffer<PanelLocals> = "PanelLocals",
+    panel: gfx::TextureSampler<[f32; 4]> = "t_Panel",
+    out: gfx::BlendTarget<Srgba8> = ("Target0", gfx::state::MASK_ALL, PREMULTIPLIED_ALPHA),
+    scissor: gfx::Scissor = (),
+});
+
+// where a ray from the camera through the point (x, y) in normalized device coordinates
+// hits the panel, in texture coordinates
+fn panel_hit(transform: [[f32; 4]; 4], x: f32, y: f32) -> Option<[f32; 2]> {
+    use cgmath::{Matrix4, SquareMatrix, Vector4};
+    let inverse = match Matrix4::from(transform).invert() {
+        Some(inverse) => inverse,
+        None => return None,
+    };
+    let unproject = |z| {
+        let p = inverse * Vector4::new(x, y, z, 1.0);
+        [p.x / p.w, p.y / p.w, p.z / p.w]
+    };
+    let (near, far) = (unproject(-1.0), unproject(1.0));
+    // the panel lies in the z = 0 plane
+    if (near[2] > 0.0) == (far[2] > 0.0) {
+        return None;
+    }
+    let t = near[2] / (near[2] - far[2]);
+    let (hx, hy) = (near[0] + t * (far[0] - near[0]), near[1] + t * (far[1] - near[1]));
+    if hx.abs() > PANEL_EXTENT || hy.abs() > PANEL_EXTENT {
+        return None;
+    }
+    Some([(hx + PANEL_EXTENT) / (2.0 * PANEL_EXTENT), (PANEL_EXTENT - hy) / (2.0 * PANEL_EXTENT)])
+}
+
 struct CubemapData<'a> {
     up: &'a [u8],
     down: &'a [u8],
@@ -78,24 +157,97 @@ impl<'a> CubemapData<'a> {
     }
 }
 
-fn load_cubemap<R, F>(factory: &mut F, data: CubemapData) -> Result<gfx::handle::ShaderResourceView<R, [f32; 4]>, String>
+// what the inspector shows about the loaded cubemap
+#[derive(Clone, Copy, Debug)]
+struct CubemapInfo {
+    size: u16,
+}
+
+impl Inspect for CubemapInfo {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        vec![
+            ("size", Property::Text(format!("{0}x{0}", self.size))),
+            ("format", Property::Text("Rgba8".to_string())),
+            ("faces", Property::Text("+x -x +y -y +z -z".to_string())),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CameraParams {
+    // radians per second
+    speed: f32,
+    // vertical field of view in degrees
+    fov: f32,
+}
+
+impl Inspect for CameraParams {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        vec![
+            ("speed", Property::Float { value: self.speed, min: 0.0, max: 1.0 }),
+            ("fov", Property::Float { value: self.fov, min: 20.0, max: 120.0 }),
+        ]
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        match (name, value) {
+            ("speed", &Property::Float { value, .. }) => self.speed = value,
+            ("fov", &Property::Float { value, .. }) => self.fov = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+struct Scene {
+    camera: Rc<Cell<CameraParams>>,
+    cubemap: CubemapInfo,
+    locals: Rc<RefCell<Constants<Locals>>>,
+}
+
+impl Inspect for Scene {
+    fn children(&self) -> Vec<(&'static str, &Inspect)> {
+        vec![("camera", &self.camera as &Inspect), ("cubemap", &self.cubemap as &Inspect), ("Locals", &self.locals as &Inspect)]
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Inspect> {
+        match name {
+            "camera" => Some(&mut self.camera),
+            "cubemap" => Some(&mut self.cubemap),
+            "Locals" => Some(&mut self.locals),
+            _ => None,
+        }
+    }
+}
+
+fn load_cubemap<R, F>(factory: &mut F, data: CubemapData) -> Result<(gfx::handle::ShaderResourceView<R, [f32; 4]>, CubemapInfo), String>
         where R: gfx::Resources, F: gfx::Factory<R>
 {
     let images = data.as_array().iter().map(|data| {
         image::load(Cursor::new(data), image::JPEG).unwrap().to_rgba()
     }).collect::<Vec<_>>();
     let data: [&[u8]; 6] = [&images[0], &images[1], &images[2], &images[3], &images[4], &images[5]];
-    let kind = gfx::tex::Kind::Cube(images[0].dimensions().0 as u16);
+    let size = images[0].dimensions().0 as u16;
+    let kind = gfx::tex::Kind::Cube(size);
     match factory.create_texture_const_u8::<Rgba8>(kind, &data) {
-        Ok((_, view)) => Ok(view),
+        Ok((_, view)) => Ok((view, CubemapInfo { size: size })),
         Err(_) => Err("Unable to create an immutable cubemap texture".to_owned()),
     }
 }
 
 struct App<R: gfx::Resources>{
     bundle: pipe::Bundle<R>,
-    projection: cgmath::Matrix4<f32>,
-    speed: Rc<Cell<f32>>,
+    camera: Rc<Cell<CameraParams>>,
+    cubemap: CubemapInfo,
+    locals: Rc<RefCell<Constants<Locals>>>,
+    panel: panel::Bundle<R>,
+    // None if the backend can't share textures with Sciter
+    panel_texture: Option<ElementTexture>,
+    // the box in normalized device coordinates, projection and view of every viewport
+    // of the last frame, to ray-cast the panel
+    panel_transforms: Rc<RefCell<Vec<([f32; 4], [[f32; 4]; 4])>>>,
+    // GL counts the scissor rows from the bottom
+    scissor_from_bottom: bool,
     view: Option<View>,
 }
 
@@ -121,7 +273,7 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
         ];
         let (vbuf, slice) = factory.create_vertex_buffer(&vertex_data);
 
-        let cubemap = load_cubemap(&mut factory, CubemapData {
+        let (cubemap, cubemap_info) = load_cubemap(&mut factory, CubemapData {
             up: &include_bytes!("image/posy.jpg")[..],
             down: &include_bytes!("image/negy.jpg")[..],
             front: &include_bytes!("image/posz.jpg")[..],
@@ -132,7 +284,9 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
 
         let sampler = factory.create_sampler_linear();
 
-        let proj = cgmath::perspective(cgmath::deg(60.0f32), init.aspect_ratio, 0.01, 100.0);
+        // the whole window until the first frame finds the viewports
+        let (width, height, _, _) = init.color.get_dimensions();
+        let scissor = gfx::target::Rect { x: 0, y: 0, w: width, h: height };
 
         let pso = factory.create_pipeline_simple(
             vs.select(init.backend).unwrap(),
@@ -146,23 +300,95 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
             cubemap: (cubemap, sampler),
             locals: factory.create_constant_buffer(1),
             out: init.color,
+            scissor: scissor,
+        };
+
+        let panel = {
+            let vs = gfx_app::shade::Source {
+                glsl_150: include_bytes!("shader/panel_150.glslv"),
+                hlsl_40:  include_bytes!("data/panel_vs.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+            let ps = gfx_app::shade::Source {
+                glsl_150: include_bytes!("shader/panel_150.glslf"),
+                hlsl_40:  include_bytes!("data/panel_ps.fx"),
+                .. gfx_app::shade::Source::empty()
+            };
+
+            let e = PANEL_EXTENT;
+            let vertex_data = [
+                PanelVertex { pos: [-e, -e, 0.0], uv: [0.0, 1.0] },
+                PanelVertex { pos: [ e, -e, 0.0], uv: [1.0, 1.0] },
+                PanelVertex { pos: [ e,  e, 0.0], uv: [1.0, 0.0] },
+                PanelVertex { pos: [-e,  e, 0.0], uv: [0.0, 0.0] },
+            ];
+            let index_data: &[u16] = &[0, 1, 2, 2, 3, 0];
+            let (vbuf, slice) = factory.create_vertex_buffer_indexed(&vertex_data, index_data);
+
+            // seen from both sides
+            let pso = factory.create_pipeline_simple(
+                vs.select(init.backend).unwrap(),
+                ps.select(init.backend).unwrap(),
+                gfx::state::CullFace::Nothing,
+                panel::new()
+            ).unwrap();
+
+            // Direct2D needs an UNORM target but writes sRGB colors, so they are sampled through an sRGB view
+            let (texture, _, _) = factory.create_render_target::<Rgba8>(PANEL_SIZE, PANEL_SIZE).unwrap();
+            let srv = {
+                use gfx::format::{ChannelSource, Swizzle};
+                let swizzle = Swizzle(ChannelSource::X, ChannelSource::Y, ChannelSource::Z, ChannelSource::W);
+                factory.view_texture_as_shader_resource::<Srgba8>(&texture, (0, 0), swizzle).unwrap()
+            };
+            let data = panel::Data {
+                vbuf: vbuf,
+                locals: factory.create_constant_buffer(1),
+                panel: (srv, factory.create_sampler_linear()),
+                out: data.out.clone(),
+                scissor: scissor,
+            };
+            (panel::bundle(slice, pso, data), ElementTexture::new(&texture))
         };
 
         App {
             bundle: pipe::bundle(slice, pso, data),
-            projection: proj,
+            panel: panel.0,
+            panel_texture: panel.1,
+            panel_transforms: Rc::new(RefCell::new(Vec::new())),
+            scissor_from_bottom: match init.backend {
+                gfx_app::shade::Backend::Hlsl(_) => false,
+                _ => true,
+            },
             view: None,
-            speed: Rc::new(Cell::new(0.25)),
+            camera: Rc::new(Cell::new(CameraParams { speed: 0.25, fov: 60.0 })),
+            cubemap: cubemap_info,
+            locals: Rc::new(RefCell::new(Constants::new())),
         }
     }
 
     fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
-        {
-            use cgmath::{AffineMatrix3, SquareMatrix, Transform, Vector3, Point3};
-            // Update camera position
-            let time = time::precise_time_s() as f32 * self.speed.get();
-            let x = time.sin();
-            let z = time.cos();
+        use cgmath::{AffineMatrix3, Matrix4, SquareMatrix, Transform, Vector3, Point3};
+
+        let (width, height, _, _) = self.bundle.data.out.get_dimensions();
+        let mut viewports = match self.view {
+            Some(ref view) => viewport::find(view.api, &view.root),
+            None => Vec::new(),
+        };
+        // the whole window if the document doesn't mark any
+        if viewports.is_empty() {
+            viewports.push(Viewport::full(width, height));
+        }
+
+        let camera = self.camera.get();
+        let time = time::precise_time_s() as f32 * camera.speed;
+        let mut transforms = Vec::with_capacity(viewports.len());
+
+        encoder.clear(&self.bundle.data.out, [0.3, 0.3, 0.3, 1.0]);
+        for viewport in &viewports {
+            // Update camera position, each viewport may look from another point of the orbit
+            let angle = time + viewport.number("yaw").unwrap_or(0.0).to_radians();
+            let x = angle.sin();
+            let z = angle.cos();
 
             let view: AffineMatrix3<f32> = Transform::look_at(
                 Point3::new(x, x / 2.0, z),
@@ -170,15 +396,28 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
                 Vector3::unit_y(),
             );
 
+            let proj = Matrix4::from(viewport.transform(width, height)) *
+                cgmath::perspective(cgmath::deg(camera.fov), viewport.aspect_ratio(), 0.01, 100.0);
             let locals = Locals {
-                inv_proj: self.projection.invert().unwrap().into(),
+                inv_proj: proj.invert().unwrap().into(),
                 view: view.mat.into(),
             };
-            encoder.update_constant_buffer(&self.bundle.data.locals, &locals);
+            self.locals.borrow_mut().update(encoder, &self.bundle.data.locals, locals);
+
+            let transform = (proj * view.mat).into();
+            encoder.update_constant_buffer(&self.panel.data.locals, &PanelLocals { transform: transform });
+            transforms.push((viewport.rect.to_ndc(width, height), transform));
+
+            let scissor = viewport.scissor(width, height, self.scissor_from_bottom);
+            self.bundle.data.scissor = scissor;
+            self.panel.data.scissor = scissor;
+            self.bundle.encode(encoder);
+            // the panel shows what Sciter rendered into its texture after the last frame
+            if self.panel_texture.is_some() {
+                self.panel.encode(encoder);
+            }
         }
-
-        encoder.clear(&self.bundle.data.out, [0.3, 0.3, 0.3, 1.0]);
-        self.bundle.encode(encoder);
+        *self.panel_transforms.borrow_mut() = transforms;
     }
 
     fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
@@ -195,19 +434,34 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
       let host = any.downcast_ref::<SciterHost>().unwrap();
 
       // load UI from html
-      let ui = include_bytes!("facade.htm");
-      host.load_html(ui, None);
+      let ui = inspect::with_inspector(include_str!("facade.htm"));
+      host.load_html(ui.as_bytes(), None);
 
       // attach root handler
       if let Some(root) = host.get_root() {
         println!("document loaded: {}", root);
 
-        let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
-        self.view = Some(View { api: api, hwnd: host.get_hwnd() });
+        let fg = root.find_first("section#fore-layer");
+        let panel = root.find_first("section#world-panel");
 
-        let handler = Handler { host: Rc::downgrade(&host.clone()), speed: self.speed.clone() };
+        if let (Ok(Some(fg)), Ok(Some(panel))) = (fg, panel) {
+          let api: &'static sciter::ISciterAPI = sciter::SciterAPI();
+          self.view = Some(View { api: api, hwnd: host.get_hwnd(), root: root.clone(), foreground: fg, panel: panel });
+        }
+
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          api: sciter::SciterAPI(),
+          hwnd: host.get_hwnd(),
+          camera: self.camera.clone(),
+          panel_transforms: self.panel_transforms.clone(),
+          scene: Scene { camera: self.camera.clone(), cubemap: self.cubemap, locals: self.locals.clone() },
+        };
         host.attach_handler(handler);
 
+        root.call_function("showCamera", &[]).ok();
+        root.call_function("inspectorRefresh", &[]).ok();
+
       } else {
         println!("oops: no root element!");
       }
@@ -224,16 +478,22 @@ impl<R: gfx::Resources> App<R> {
     }
     use sciter::types::BOOL;
     let view = self.view.as_ref().unwrap();
-    let el = 0 as sciter::HELEMENT;
-    (view.api.SciterRenderOnDirectXWindow)(view.hwnd, el, false as BOOL);
-    // assert_eq!(ok, 1);
+    // the panel goes into its texture, the rest of the document stays on top of the scene
+    if let Some(ref texture) = self.panel_texture {
+      texture.render(view.api, view.hwnd, &view.panel);
+    }
+    (view.api.SciterRenderOnDirectXWindow)(view.hwnd, view.foreground.as_ptr(), true as BOOL);
   }
 }
 
 #[allow(dead_code)]
 struct Handler {
   host: Weak<sciter::Host>,
-  speed: Rc<Cell<f32>>,
+  api: &'static sciter::ISciterAPI,
+  hwnd: sciter::types::HWINDOW,
+  camera: Rc<Cell<CameraParams>>,
+  panel_transforms: Rc<RefCell<Vec<([f32; 4], [[f32; 4]; 4])>>>,
+  scene: Scene,
 }
 
 impl sciter::EventHandler for Handler {
@@ -244,9 +504,45 @@ impl sciter::EventHandler for Handler {
     match name {
       "setSpeed" => {
         let id = args[0].to_float().unwrap();
-        self.speed.set(id as f32);
+        let mut camera = self.camera.get();
+        camera.speed = id as f32;
+        self.camera.set(camera);
         Some(ok)
       },
+      "getCamera" => {
+        let camera = self.camera.get();
+        Some(vec![sciter::Value::from(camera.speed as f64), sciter::Value::from(camera.fov as f64)].into_iter().collect())
+      },
+      "panelHit" => {
+        // texture coordinates of the panel under the point, in normalized device coordinates,
+        // as seen in the topmost viewport containing it
+        let (x, y) = (args[0].to_float().unwrap() as f32, args[1].to_float().unwrap() as f32);
+        let transforms = self.panel_transforms.borrow();
+        let hit = transforms.iter().rev()
+          .find(|&&(rect, _)| x >= rect[0] && x <= rect[0] + rect[2] && y >= rect[1] && y <= rect[1] + rect[3])
+          .and_then(|&(_, transform)| panel_hit(transform, x, y));
+        Some(match hit {
+          Some(uv) => uv.iter().map(|&c| c as f64).collect(),
+          None => sciter::Value::new(),
+        })
+      },
+      "panelMouse" => {
+        // `(event, uid, x, y, view x, view y, pressed)`, for the panel element under the pointer
+        let event = MouseEvent::from_name(&args[0].as_string().unwrap());
+        let number = |i: usize| args[i].to_int().unwrap();
+        let handled = event.map_or(false, |event| offscreen::send_mouse(self.api, self.hwnd, number(1) as u32,
+          event, [number(2), number(3)], [number(4), number(5)], args[6].to_bool().unwrap()));
+        Some(sciter::Value::from(handled))
+      },
+      "inspect" => {
+        Some(inspect::to_value("scene", &self.scene))
+      },
+      "setProperty" => {
+        let path: Vec<String> = args[0].as_string().unwrap().split('/')
+          .filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+        let name = args[1].as_string().unwrap();
+        Some(sciter::Value::from(inspect::set_property(&mut self.scene, &path, &name, &args[2])))
+      },
       _ => None,
     }
   }
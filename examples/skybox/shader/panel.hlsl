@@ -0,0 +1,20 @@
+struct VsOutput {
+	float4 pos: SV_Position;
+	float2 uv: TEXCOORD;
+};
+
+cbuffer PanelLocals {
+	float4x4 u_Transform;
+};
+
+VsOutput Vertex(float3 pos: a_Pos, float2 uv: a_Uv) {
+	VsOutput output = { mul(u_Transform, float4(pos, 1.0)), uv };
+	return output;
+}
+
+Texture2D<float4> t_Panel;
+SamplerState t_Panel_;
+
+float4 Pixel(VsOutput pin): SV_Target {
+	return t_Panel.Sample(t_Panel_, pin.uv);
+}
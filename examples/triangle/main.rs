@@ -16,9 +16,18 @@
 extern crate gfx;
 extern crate gfx_app;
 extern crate sciter;
+extern crate rustc_serialize;
+extern crate gfx_sciter_examples;
 
 use std::rc::Rc;
 use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+
+mod pso;
+
+use pso::{PsoCache, PsoKey, TOPOLOGIES};
+use gfx_sciter_examples::{path_from_url, to_number};
 
 type SciterHost = Rc<sciter::Host>;
 
@@ -29,11 +38,67 @@ gfx_vertex_struct!( Vertex {
     color: [f32; 3] = "a_Color",
 });
 
+gfx_constant_struct!( Locals {
+    transform: [[f32; 4]; 4] = "u_Transform",
+    hue: f32 = "u_Hue",
+});
+
+// the GL shaders use the globals, the HLSL ones the constant buffer
 gfx_pipeline!(pipe {
     vbuf: gfx::VertexBuffer<Vertex> = (),
-    out: gfx::RenderTarget<gfx::format::Srgba8> = "Target0",
+    transform: gfx::Global<[[f32; 4]; 4]> = "u_Transform",
+    hue: gfx::Global<f32> = "u_Hue",
+    locals: gfx::ConstantBuffer<Locals> = "Locals",
+    // the blend and depth states are replaced by the ones of the PSO cache
+    out: gfx::BlendTarget<gfx::format::Srgba8> = ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::REPLACE),
+    depth: gfx::DepthTarget<gfx::format::DepthStencil> = gfx::preset::depth::PASS_TEST,
 });
 
+// the editable triangle
+const NUM_VERTICES: usize = 3;
+// the triangle edges, drawn with the line list topology
+const EDGES: [u16; 6] = [0, 1, 1, 2, 2, 0];
+// how close to a vertex, in normalized device coordinates, a click picks it
+const PICK_RADIUS: f32 = 0.1;
+
+// the on-disk form of a vertex
+#[derive(Clone, Copy, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+struct VertexDesc {
+    pos: [f32; 2],
+    color: [f32; 3],
+}
+
+fn save_vertices(path: &str, vertices: &[Vertex]) -> std::io::Result<()> {
+    use std::io::Write;
+    let desc: Vec<_> = vertices.iter().map(|v| VertexDesc { pos: v.pos, color: v.color }).collect();
+    let text = rustc_serialize::json::as_pretty_json(&desc).to_string();
+    let mut file = try!(std::fs::File::create(path));
+    file.write_all(text.as_bytes())
+}
+
+fn load_vertices(path: &str) -> std::io::Result<Vec<Vertex>> {
+    use std::io::{Error, ErrorKind, Read};
+    let mut text = String::new();
+    try!(std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut text)));
+    let desc: Vec<VertexDesc> = try!(rustc_serialize::json::decode(&text)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}", e))));
+    if desc.len() != NUM_VERTICES {
+        return Err(Error::new(ErrorKind::InvalidData, format!("expected {} vertices", NUM_VERTICES)));
+    }
+    Ok(desc.iter().map(|d| Vertex { pos: d.pos, color: d.color }).collect())
+}
+
+// rotation around Z, column major
+fn rotation(angle: f32) -> [[f32; 4]; 4] {
+    let (s, c) = angle.sin_cos();
+    [
+        [  c,   s, 0.0, 0.0],
+        [ -s,   c, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
 
 struct View {
     api: &'static sciter::ISciterAPI,
@@ -43,9 +108,22 @@ struct View {
 }
 
 struct App<R: gfx::Resources> {
-    pso: gfx::PipelineState<R, pipe::Meta>,
+    psos: PsoCache<R>,
+    // the state selected in the UI
+    pso_key: Rc<Cell<PsoKey>>,
     data: pipe::Data<R>,
     slice: gfx::Slice<R>,
+    edges: gfx::Slice<R>,
+    // edited from the UI, uploaded to the vertex buffer when dirty
+    vertices: Rc<RefCell<Vec<Vertex>>>,
+    vertices_dirty: Rc<Cell<bool>>,
+    // radians per second, set from the UI
+    rotation_speed: Rc<Cell<f32>>,
+    color_speed: Rc<Cell<f32>>,
+    // current angles in radians, the rotation is needed to pick vertices
+    rotation: Rc<Cell<f32>>,
+    hue: f32,
+    last_frame: Option<Instant>,
     view: Option<View>,
 }
 
@@ -71,20 +149,46 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
             Vertex { pos: [  0.5, -0.5 ], color: [0.0, 1.0, 0.0] },
             Vertex { pos: [  0.0,  0.5 ], color: [0.0, 0.0, 1.0] },
         ];
-        let (vbuf, slice) = factory.create_vertex_buffer(&vertex_data);
+        // the vertices can be edited, so the buffer is dynamic. it is filled on the first frame.
+        let vbuf = factory.create_buffer_dynamic(NUM_VERTICES, gfx::BufferRole::Vertex, gfx::Bind::empty()).unwrap();
+        let slice = gfx::Slice {
+            start: 0,
+            end: NUM_VERTICES as gfx::VertexCount,
+            base_vertex: 0,
+            instances: None,
+            buffer: gfx::IndexBuffer::Auto,
+        };
+        let ibuf = factory.create_buffer_static(&EDGES, gfx::BufferRole::Index, gfx::Bind::empty()).unwrap();
+        let edges = gfx::Slice {
+            start: 0,
+            end: EDGES.len() as gfx::VertexCount,
+            base_vertex: 0,
+            instances: None,
+            buffer: gfx::IndexBuffer::Index16(ibuf),
+        };
+
+        let psos = PsoCache::new(&mut factory, vs.select(init.backend).unwrap(), fs.select(init.backend).unwrap());
 
         App {
-            pso: factory.create_pipeline_simple(
-                vs.select(init.backend).unwrap(),
-                fs.select(init.backend).unwrap(),
-                gfx::state::CullFace::Nothing,
-                pipe::new()
-                ).unwrap(),
+            psos: psos,
+            pso_key: Rc::new(Cell::new(PsoKey::default())),
             data: pipe::Data {
                 vbuf: vbuf,
+                transform: rotation(0.0),
+                hue: 0.0,
+                locals: factory.create_constant_buffer(1),
                 out: init.color,
+                depth: init.depth,
             },
             slice: slice,
+            edges: edges,
+            vertices: Rc::new(RefCell::new(vertex_data.to_vec())),
+            vertices_dirty: Rc::new(Cell::new(true)),
+            rotation_speed: Rc::new(Cell::new(1.0)),
+            color_speed: Rc::new(Cell::new(1.0)),
+            rotation: Rc::new(Cell::new(0.0)),
+            hue: 0.0,
+            last_frame: None,
             view: Default::default(),
         }
     }
@@ -112,7 +216,15 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
           self.view = Some(View { api: api, hwnd: host.get_hwnd(), background: bg.unwrap().unwrap(), foreground: fg.unwrap().unwrap() });
         }
 
-        let handler = Handler { host: Rc::downgrade(&host.clone()) };
+        let handler = Handler {
+          host: Rc::downgrade(&host.clone()),
+          rotation_speed: self.rotation_speed.clone(),
+          color_speed: self.color_speed.clone(),
+          rotation: self.rotation.clone(),
+          vertices: self.vertices.clone(),
+          vertices_dirty: self.vertices_dirty.clone(),
+          pso_key: self.pso_key.clone(),
+        };
         host.attach_handler(handler);
 
       } else {
@@ -122,12 +234,41 @@ impl<R: gfx::Resources> gfx_app::Application<R> for App<R> {
 
     fn render_pre<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) -> bool {
       encoder.clear(&self.data.out, [0.1, 0.2, 0.3, 1.0]);
+      encoder.clear_depth(&self.data.depth, 1.0);
       return true;
     }
 
     fn render<C: gfx::CommandBuffer<R>>(&mut self, encoder: &mut gfx::Encoder<R, C>) {
       self.render_layer(false);
-      encoder.draw(&self.slice, &self.pso, &self.data);
+
+      // advance the animation by the time since the last frame
+      let now = Instant::now();
+      if let Some(last) = self.last_frame {
+        let elapsed = now.duration_since(last);
+        let dt = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1000000000.0;
+        let two_pi = 2.0 * ::std::f32::consts::PI;
+        self.rotation.set((self.rotation.get() + self.rotation_speed.get() * dt) % two_pi);
+        self.hue = (self.hue + self.color_speed.get() * dt) % two_pi;
+      }
+      self.last_frame = Some(now);
+
+      if self.vertices_dirty.get() {
+        self.vertices_dirty.set(false);
+        encoder.update_buffer(&self.data.vbuf, &self.vertices.borrow(), 0).unwrap();
+      }
+
+      self.data.transform = rotation(self.rotation.get());
+      self.data.hue = self.hue;
+      encoder.update_constant_buffer(&self.data.locals, &Locals {
+        transform: self.data.transform,
+        hue: self.hue,
+      });
+      let key = self.pso_key.get();
+      let slice = match TOPOLOGIES[key.topology].1 {
+        gfx::Primitive::LineList => &self.edges,
+        _ => &self.slice,
+      };
+      encoder.draw(slice, self.psos.get(key), &self.data);
     }
 
     fn render_post<C: gfx::CommandBuffer<R>>(&mut self, _encoder: &mut gfx::Encoder<R, C>) -> bool {
@@ -171,6 +312,20 @@ impl<R: gfx::Resources> App<R> {
 #[allow(dead_code)]
 struct Handler {
   host: ::std::rc::Weak<sciter::Host>,
+  rotation_speed: Rc<Cell<f32>>,
+  color_speed: Rc<Cell<f32>>,
+  rotation: Rc<Cell<f32>>,
+  vertices: Rc<RefCell<Vec<Vertex>>>,
+  vertices_dirty: Rc<Cell<bool>>,
+  pso_key: Rc<Cell<PsoKey>>,
+}
+
+impl Handler {
+  // undoes the current rotation of a point in normalized device coordinates
+  fn to_model(&self, x: f32, y: f32) -> [f32; 2] {
+    let (s, c) = self.rotation.get().sin_cos();
+    [c * x + s * y, -s * x + c * y]
+  }
 }
 
 impl sciter::EventHandler for Handler {
@@ -179,13 +334,88 @@ impl sciter::EventHandler for Handler {
   fn on_script_call(&mut self, root: sciter::HELEMENT, name: &str, args: &[sciter::Value]) -> Option<sciter::Value> {
     use sciter::Value;
 
-    let args = args.iter().map(|ref x| format!("{}", &x)).collect::<Vec<String>>().join(", ");
-    println!("script->native: {}({}), root {:?}", name, args, root);
+    let printed = args.iter().map(|ref x| format!("{}", &x)).collect::<Vec<String>>().join(", ");
+    println!("script->native: {}({}), root {:?}", name, printed, root);
 
     let ok = Value::from(true);
     match name {
-      "setRotationSpeed" => Some(ok),
-      "setColorSpeed" => Some(ok),
+      "setRotationSpeed" => {
+        self.rotation_speed.set(to_number(&args[0]).unwrap() as f32);
+        Some(ok)
+      },
+      "setColorSpeed" => {
+        self.color_speed.set(to_number(&args[0]).unwrap() as f32);
+        Some(ok)
+      },
+      "pickVertex" => {
+        // index of the vertex under the point, or -1
+        let p = self.to_model(to_number(&args[0]).unwrap() as f32, to_number(&args[1]).unwrap() as f32);
+        let vertices = self.vertices.borrow();
+        let nearest = vertices.iter().enumerate()
+          .map(|(i, v)| (i, (v.pos[0] - p[0]).hypot(v.pos[1] - p[1])))
+          .filter(|&(_, d)| d < PICK_RADIUS)
+          .fold(None, |best: Option<(usize, f32)>, (i, d)| match best {
+            Some((_, bd)) if bd <= d => best,
+            _ => Some((i, d)),
+          });
+        Some(Value::from(nearest.map_or(-1, |(i, _)| i as i32)))
+      },
+      "moveVertex" => {
+        let index = args[0].to_int().unwrap() as usize;
+        let p = self.to_model(to_number(&args[1]).unwrap() as f32, to_number(&args[2]).unwrap() as f32);
+        if let Some(v) = self.vertices.borrow_mut().get_mut(index) {
+          v.pos = [p[0].max(-1.0).min(1.0), p[1].max(-1.0).min(1.0)];
+          self.vertices_dirty.set(true);
+        }
+        Some(ok)
+      },
+      "getVertexColor" => {
+        let index = args[0].to_int().unwrap() as usize;
+        self.vertices.borrow().get(index).map(|v| {
+          v.color.iter().map(|&x| x as f64).collect::<Value>()
+        })
+      },
+      "setVertexColor" => {
+        let index = args[0].to_int().unwrap() as usize;
+        if let Some(v) = self.vertices.borrow_mut().get_mut(index) {
+          for i in 0..3 {
+            v.color[i] = (to_number(&args[i + 1]).unwrap() as f32).max(0.0).min(1.0);
+          }
+          self.vertices_dirty.set(true);
+        }
+        Some(ok)
+      },
+      "saveVertices" => {
+        let path = args[0].as_string().unwrap();
+        match save_vertices(&path_from_url(&path), &self.vertices.borrow()) {
+          Ok(_) => Some(ok),
+          Err(e) => {
+            println!("unable to save the vertices to {}: {}", path, e);
+            Some(Value::from(false))
+          }
+        }
+      },
+      "loadVertices" => {
+        let path = args[0].as_string().unwrap();
+        match load_vertices(&path_from_url(&path)) {
+          Ok(vertices) => {
+            *self.vertices.borrow_mut() = vertices;
+            self.vertices_dirty.set(true);
+            Some(ok)
+          },
+          Err(e) => {
+            println!("unable to load the vertices from {}: {}", path, e);
+            Some(Value::from(false))
+          }
+        }
+      },
+      "setPipelineState" => {
+        // topology, cull face, fill mode, blend and depth test indices
+        let index = |i: usize| args[i].to_int().unwrap().max(0) as usize;
+        let key = PsoKey { topology: index(0), cull: index(1), fill: index(2), blend: index(3), depth: index(4) };
+        self.pso_key.set(key.clamped());
+        Some(ok)
+      },
       _ => None,
     }
   }
@@ -0,0 +1,148 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Pipeline states selectable at runtime.
+//
+// A pipeline state object bakes in the primitive topology, the rasterizer and the output
+// merger states, so every combination needs its own PSO. They all share one linked program
+// and are kept in a cache keyed by the state indices, the indices being the ones of the
+// tables below and of the `<option>`s in the facade.
+//
+// The factory is only available while the application is created, so the cache can't create
+// the states on first use and is filled up front; toggling a state is then a lookup. Cull face
+// and fill mode only apply to triangles, so lines and points share one state per blend and
+// depth test: 2 * 3 * 3 * 4 * 2 + 3 * 4 * 2 = 168 pipeline states from one linked program.
+//
+// D3D11 rasterizes only solid and wireframe polygons, its backend draws the point fill mode as
+// wireframe.
+
+use std::collections::HashMap;
+use gfx;
+use gfx::state::{CullFace, RasterMethod, Rasterizer};
+
+use pipe;
+
+pub const TOPOLOGIES: [(&'static str, gfx::Primitive); 5] = [
+    ("Triangle list", gfx::Primitive::TriangleList),
+    ("Triangle strip", gfx::Primitive::TriangleStrip),
+    ("Line list", gfx::Primitive::LineList),
+    ("Line strip", gfx::Primitive::LineStrip),
+    ("Points", gfx::Primitive::PointList),
+];
+
+pub const CULL_FACES: [(&'static str, CullFace); 3] = [
+    ("None", CullFace::Nothing),
+    ("Front", CullFace::Front),
+    ("Back", CullFace::Back),
+];
+
+pub const FILL_MODES: [(&'static str, RasterMethod); 3] = [
+    ("Solid", RasterMethod::Fill),
+    ("Wireframe", RasterMethod::Line(1)),
+    ("Points", RasterMethod::Point),
+];
+
+pub const BLENDS: [(&'static str, gfx::state::Blend); 4] = [
+    ("Replace", gfx::preset::blend::REPLACE),
+    ("Alpha", gfx::preset::blend::ALPHA),
+    ("Add", gfx::preset::blend::ADD),
+    ("Multiply", gfx::preset::blend::MULTIPLY),
+];
+
+pub const DEPTH_TESTS: [(&'static str, gfx::state::Depth); 2] = [
+    ("Off", gfx::preset::depth::PASS_TEST),
+    ("Less or equal", gfx::preset::depth::LESS_EQUAL_WRITE),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PsoKey {
+    pub topology: usize,
+    pub cull: usize,
+    pub fill: usize,
+    pub blend: usize,
+    pub depth: usize,
+}
+
+impl PsoKey {
+    /// Every distinct key, see `canonical`.
+    pub fn all() -> Vec<PsoKey> {
+        let mut keys = Vec::new();
+        for topology in 0..TOPOLOGIES.len() {
+            for cull in 0..CULL_FACES.len() {
+                for fill in 0..FILL_MODES.len() {
+                    for blend in 0..BLENDS.len() {
+                        for depth in 0..DEPTH_TESTS.len() {
+                            let key = PsoKey { topology: topology, cull: cull, fill: fill, blend: blend, depth: depth };
+                            if key.canonical() == key {
+                                keys.push(key);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        keys
+    }
+
+    /// The key of the state drawing the same thing, lines and points ignore the cull face and
+    /// the fill mode.
+    pub fn canonical(self) -> PsoKey {
+        match TOPOLOGIES[self.topology].1 {
+            gfx::Primitive::TriangleList | gfx::Primitive::TriangleStrip => self,
+            _ => PsoKey { cull: 0, fill: 0, .. self },
+        }
+    }
+
+    /// Clamps the indices into the tables, for keys coming from the UI.
+    pub fn clamped(self) -> PsoKey {
+        PsoKey {
+            topology: self.topology.min(TOPOLOGIES.len() - 1),
+            cull: self.cull.min(CULL_FACES.len() - 1),
+            fill: self.fill.min(FILL_MODES.len() - 1),
+            blend: self.blend.min(BLENDS.len() - 1),
+            depth: self.depth.min(DEPTH_TESTS.len() - 1),
+        }
+    }
+}
+
+pub struct PsoCache<R: gfx::Resources> {
+    states: HashMap<PsoKey, gfx::PipelineState<R, pipe::Meta>>,
+}
+
+impl<R: gfx::Resources> PsoCache<R> {
+    pub fn new<F: gfx::Factory<R>>(factory: &mut F, vs: &[u8], ps: &[u8]) -> PsoCache<R> {
+        use gfx::traits::FactoryExt;
+
+        let program = factory.link_program(vs, ps).unwrap();
+        let states = PsoKey::all().into_iter().map(|key| {
+            let rasterizer = Rasterizer {
+                cull_face: CULL_FACES[key.cull].1,
+                method: FILL_MODES[key.fill].1,
+                .. Rasterizer::new_fill()
+            };
+            let init = pipe::Init {
+                out: ("Target0", gfx::state::MASK_ALL, BLENDS[key.blend].1),
+                depth: DEPTH_TESTS[key.depth].1,
+                .. pipe::new()
+            };
+            let pso = factory.create_pipeline_from_program(&program, TOPOLOGIES[key.topology].1, rasterizer, init).unwrap();
+            (key, pso)
+        }).collect();
+        PsoCache { states: states }
+    }
+
+    pub fn get(&self, key: PsoKey) -> &gfx::PipelineState<R, pipe::Meta> {
+        &self.states[&key.canonical()]
+    }
+}
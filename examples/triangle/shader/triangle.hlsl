@@ -2,11 +2,23 @@ struct VsOutput {
     float4 pos: SV_Position;
     float3 color: COLOR;
 };
- 
-VsOutput Vertex(float3 pos : a_Pos, float3 color : a_Color) {
+
+cbuffer Locals {
+    float4x4 u_Transform;
+    float u_Hue;
+};
+
+// rotates the color around the gray axis
+float3 hue_shift(float3 color, float angle) {
+    const float3 k = 0.57735.xxx;
+    float c = cos(angle);
+    return color * c + cross(k, color) * sin(angle) + k * dot(k, color) * (1.0 - c);
+}
+
+VsOutput Vertex(float2 pos : a_Pos, float3 color : a_Color) {
     VsOutput output = {
-        float4(pos, 1.0),
-        color,
+        mul(u_Transform, float4(pos, 0.0, 1.0)),
+        hue_shift(color, u_Hue),
     };
     return output;
 }
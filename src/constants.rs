@@ -0,0 +1,207 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Inspector controls generated from constant structs.
+//!
+//! `inspect_constant_struct!` takes the same definition as `gfx_constant_struct!` and also
+//! implements `Inspect` for the struct: scalars become sliders, vectors get one slider per
+//! component and matrices are shown read-only. A field can be given a slider range with
+//! `=> (min, max)` after its shader name.
+//!
+//! The renderer keeps computing its constants every frame, `Constants` lays the edits made
+//! in the inspector over them before they go to the constant buffer.
+
+use gfx;
+use inspect::{Inspect, Property};
+
+/// A field of a constant struct, as seen by the inspector.
+pub trait ConstantField: Copy {
+    /// `range` is the slider range given in the struct definition, if any.
+    fn to_property(&self, range: Option<(f32, f32)>) -> Property;
+    fn set_property(&mut self, value: &Property) -> bool;
+}
+
+impl ConstantField for f32 {
+    fn to_property(&self, range: Option<(f32, f32)>) -> Property {
+        let (min, max) = range.unwrap_or((0.0, 1.0));
+        Property::Float { value: *self, min: min, max: max }
+    }
+
+    fn set_property(&mut self, value: &Property) -> bool {
+        match *value {
+            Property::Float { value, .. } => { *self = value; true },
+            _ => false,
+        }
+    }
+}
+
+impl ConstantField for i32 {
+    fn to_property(&self, range: Option<(f32, f32)>) -> Property {
+        let (min, max) = range.unwrap_or((0.0, 16.0));
+        Property::Int { value: *self, min: min as i32, max: max as i32 }
+    }
+
+    fn set_property(&mut self, value: &Property) -> bool {
+        match *value {
+            Property::Int { value, .. } => { *self = value; true },
+            _ => false,
+        }
+    }
+}
+
+impl ConstantField for u32 {
+    fn to_property(&self, range: Option<(f32, f32)>) -> Property {
+        let (min, max) = range.unwrap_or((0.0, 16.0));
+        Property::Int { value: *self as i32, min: min.max(0.0) as i32, max: max as i32 }
+    }
+
+    fn set_property(&mut self, value: &Property) -> bool {
+        match *value {
+            Property::Int { value, .. } if value >= 0 => { *self = value as u32; true },
+            _ => false,
+        }
+    }
+}
+
+macro_rules! impl_vector {
+    ($($n:expr),*) => { $(
+        impl ConstantField for [f32; $n] {
+            fn to_property(&self, range: Option<(f32, f32)>) -> Property {
+                let (min, max) = range.unwrap_or((-1.0, 1.0));
+                Property::Vector { value: self.to_vec(), min: min, max: max }
+            }
+
+            fn set_property(&mut self, value: &Property) -> bool {
+                match *value {
+                    Property::Vector { ref value, .. } if value.len() == $n => {
+                        self.copy_from_slice(value);
+                        true
+                    },
+                    _ => false,
+                }
+            }
+        }
+    )* }
+}
+
+impl_vector!(2, 3, 4);
+
+impl ConstantField for [[f32; 4]; 4] {
+    fn to_property(&self, _: Option<(f32, f32)>) -> Property {
+        Property::Matrix(*self)
+    }
+
+    fn set_property(&mut self, _: &Property) -> bool {
+        false
+    }
+}
+
+/// Defines a constant struct with `gfx_constant_struct!` and makes it inspectable.
+///
+/// ```ignore
+/// inspect_constant_struct!( Locals {
+///     transform: [[f32; 4]; 4] = "u_Transform",
+///     hue: f32 = "u_Hue" => (0.0, 360.0),
+/// });
+/// ```
+#[macro_export]
+macro_rules! inspect_constant_struct {
+    ($root:ident { $( $field:ident : $ty:ty = $name:expr $(=> ($min:expr, $max:expr))* , )* }) => {
+        gfx_constant_struct!( $root {
+            $( $field: $ty = $name, )*
+        });
+
+        impl $crate::inspect::Inspect for $root {
+            fn properties(&self) -> Vec<(&'static str, $crate::inspect::Property)> {
+                use $crate::constants::ConstantField;
+                vec![ $(
+                    (stringify!($field), self.$field.to_property(None $(.or(Some(($min as f32, $max as f32))))*)),
+                )* ]
+            }
+
+            fn set_property(&mut self, name: &str, value: &$crate::inspect::Property) -> bool {
+                use $crate::constants::ConstantField;
+                match name {
+                    $( stringify!($field) => self.$field.set_property(value), )*
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// The contents of a constant buffer along with the fields edited in the inspector.
+///
+/// Besides the struct fields, an `edited` checkbox tells whether any of them is overridden;
+/// clearing it drops the edits.
+pub struct Constants<T: Copy> {
+    current: Option<T>,
+    edits: Vec<(&'static str, Property)>,
+}
+
+impl<T: Copy + Inspect> Constants<T> {
+    pub fn new() -> Constants<T> {
+        Constants {
+            current: None,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Applies the edits to `value` and writes the result into `buffer`.
+    pub fn update<R, C>(&mut self, encoder: &mut gfx::Encoder<R, C>, buffer: &gfx::handle::Buffer<R, T>, mut value: T) -> T
+        where R: gfx::Resources, C: gfx::CommandBuffer<R>
+    {
+        for &(name, ref property) in &self.edits {
+            value.set_property(name, property);
+        }
+        self.current = Some(value);
+        encoder.update_constant_buffer(buffer, &value);
+        value
+    }
+}
+
+impl<T: Copy + Inspect> Inspect for Constants<T> {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        let mut properties = vec![("edited", Property::Bool(!self.edits.is_empty()))];
+        if let Some(ref current) = self.current {
+            properties.extend(current.properties());
+        }
+        properties
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        if name == "edited" {
+            if *value == Property::Bool(false) {
+                self.edits.clear();
+            }
+            return true;
+        }
+        let current = match self.current {
+            Some(ref mut current) => current,
+            None => return false,
+        };
+        // the edits keep the field name given by the struct
+        let field = match current.properties().into_iter().find(|&(n, _)| n == name) {
+            Some((field, _)) => field,
+            None => return false,
+        };
+        if !current.set_property(field, value) {
+            return false;
+        }
+        self.edits.retain(|&(n, _)| n != field);
+        self.edits.push((field, value.clone()));
+        true
+    }
+}
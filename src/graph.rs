@@ -0,0 +1,229 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! A small render graph.
+//!
+//! Passes declare the resources they read and write, by name, and which of them they clear
+//! first. `RenderGraph::new` derives the execution order from that:
+//!
+//! * a pass reading a resource runs after every pass writing it,
+//! * a pass clearing a resource runs before the other passes writing it,
+//! * otherwise the writers of a resource keep the order they were declared in.
+//!
+//! A resource is expected to be fully written before it's read, passes don't overwrite
+//! what was already read in the same frame. The graph only orders the passes, encoding
+//! them is up to the caller, which usually matches on the pass names.
+
+use sciter::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pass {
+    pub name: &'static str,
+    pub inputs: Vec<&'static str>,
+    pub outputs: Vec<&'static str>,
+    /// Cleared before the pass, always a subset of `outputs`.
+    pub clears: Vec<&'static str>,
+}
+
+impl Pass {
+    pub fn new(name: &'static str) -> Pass {
+        Pass {
+            name: name,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            clears: Vec::new(),
+        }
+    }
+
+    pub fn reads(mut self, resources: &[&'static str]) -> Pass {
+        self.inputs.extend_from_slice(resources);
+        self
+    }
+
+    pub fn writes(mut self, resources: &[&'static str]) -> Pass {
+        self.outputs.extend_from_slice(resources);
+        self
+    }
+
+    /// Writes the resources, clearing them first.
+    pub fn clears(mut self, resources: &[&'static str]) -> Pass {
+        self.clears.extend_from_slice(resources);
+        self.writes(resources)
+    }
+}
+
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+    // indices into `passes`, in execution order
+    order: Vec<usize>,
+    // the passes each pass has to run after
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl RenderGraph {
+    /// Orders the passes, failing if they depend on each other in a cycle.
+    pub fn new(passes: Vec<Pass>) -> Result<RenderGraph, String> {
+        let mut dependencies = vec![Vec::new(); passes.len()];
+        for (j, pass) in passes.iter().enumerate() {
+            for (i, other) in passes.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let reads = pass.inputs.iter().any(|r| other.outputs.contains(r));
+                let both_write = pass.outputs.iter().any(|r| other.outputs.contains(r));
+                let after = reads || (both_write && {
+                    let (mine, theirs) = (
+                        pass.outputs.iter().any(|r| pass.clears.contains(r) && other.outputs.contains(r)),
+                        other.outputs.iter().any(|r| other.clears.contains(r) && pass.outputs.contains(r)),
+                    );
+                    // the one clearing goes first, declaration order otherwise
+                    if mine != theirs { theirs } else { i < j }
+                });
+                if after {
+                    dependencies[j].push(i);
+                }
+            }
+        }
+
+        // Kahn's algorithm, preferring the pass declared first among the ready ones
+        let mut order = Vec::with_capacity(passes.len());
+        let mut done = vec![false; passes.len()];
+        while order.len() < passes.len() {
+            let next = (0 .. passes.len()).find(|&j| !done[j] && dependencies[j].iter().all(|&i| done[i]));
+            match next {
+                Some(j) => {
+                    done[j] = true;
+                    order.push(j);
+                },
+                None => {
+                    let stuck: Vec<&str> = (0 .. passes.len()).filter(|&j| !done[j]).map(|j| passes[j].name).collect();
+                    return Err(format!("render graph has a cycle between {}", stuck.join(", ")));
+                },
+            }
+        }
+
+        Ok(RenderGraph {
+            passes: passes,
+            order: order,
+            dependencies: dependencies,
+        })
+    }
+
+    /// The passes in execution order.
+    pub fn passes(&self) -> Vec<&Pass> {
+        self.order.iter().map(|&i| &self.passes[i]).collect()
+    }
+
+    // longest chain of dependencies leading to each pass, indexed like `passes`
+    fn levels(&self) -> Vec<usize> {
+        let mut levels = vec![0; self.passes.len()];
+        for &j in &self.order {
+            levels[j] = self.dependencies[j].iter().map(|&i| levels[i] + 1).max().unwrap_or(0);
+        }
+        levels
+    }
+
+    /// For the diagram: `[[[name, level, inputs, outputs, clears, enabled], ...], [[from, to, resource], ...]]`,
+    /// passes in execution order and the edges between them indexing into that list.
+    /// Edges are only made for the resources read, `enabled` tells which passes run this frame.
+    pub fn to_value(&self, enabled: &Fn(&str) -> bool) -> Value {
+        fn strings(list: &[&'static str]) -> Value {
+            list.iter().map(|&s| Value::from(s)).collect()
+        }
+
+        let levels = self.levels();
+        let position = |i: usize| self.order.iter().position(|&j| j == i).unwrap();
+        let passes: Value = self.order.iter().map(|&i| {
+            let pass = &self.passes[i];
+            vec![
+                Value::from(pass.name),
+                Value::from(levels[i] as i32),
+                strings(&pass.inputs),
+                strings(&pass.outputs),
+                strings(&pass.clears),
+                Value::from(enabled(pass.name)),
+            ].into_iter().collect::<Value>()
+        }).collect();
+
+        let mut edges = Vec::new();
+        for (j, pass) in self.passes.iter().enumerate() {
+            for &i in &self.dependencies[j] {
+                for &resource in pass.inputs.iter().filter(|r| self.passes[i].outputs.contains(r)) {
+                    edges.push(vec![
+                        Value::from(position(i) as i32),
+                        Value::from(position(j) as i32),
+                        Value::from(resource),
+                    ].into_iter().collect::<Value>());
+                }
+            }
+        }
+        vec![passes, edges.into_iter().collect()].into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pass, RenderGraph};
+
+    fn names(graph: &RenderGraph) -> Vec<&'static str> {
+        graph.passes().iter().map(|p| p.name).collect()
+    }
+
+    #[test]
+    fn readers_run_after_writers() {
+        let graph = RenderGraph::new(vec![
+            Pass::new("tonemap").reads(&["hdr"]).writes(&["frame"]),
+            Pass::new("light").reads(&["gbuffer"]).writes(&["hdr"]),
+            Pass::new("geometry").writes(&["gbuffer"]),
+        ]).unwrap();
+        assert_eq!(names(&graph), ["geometry", "light", "tonemap"]);
+    }
+
+    #[test]
+    fn clearing_pass_runs_first() {
+        let graph = RenderGraph::new(vec![
+            Pass::new("emitters").writes(&["hdr"]),
+            Pass::new("sun").clears(&["hdr"]),
+            Pass::new("lights").writes(&["hdr"]),
+        ]).unwrap();
+        assert_eq!(names(&graph), ["sun", "emitters", "lights"]);
+    }
+
+    #[test]
+    fn writers_keep_the_declaration_order() {
+        let graph = RenderGraph::new(vec![
+            Pass::new("b").writes(&["target"]),
+            Pass::new("a").writes(&["target"]),
+            Pass::new("c").writes(&["other"]),
+        ]).unwrap();
+        assert_eq!(names(&graph), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let cycle = RenderGraph::new(vec![
+            Pass::new("a").reads(&["y"]).writes(&["x"]),
+            Pass::new("b").reads(&["x"]).writes(&["y"]),
+        ]);
+        assert!(cycle.is_err());
+
+        // the clearing pass goes first, but it also reads what the other one writes
+        let read_write = RenderGraph::new(vec![
+            Pass::new("first").clears(&["target"]).reads(&["target"]),
+            Pass::new("second").writes(&["target"]),
+        ]);
+        assert!(read_write.is_err());
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Live images of render targets inside the document.
+//!
+//! A `<gfx-image target="name">` element shows the shader resource view registered under
+//! `name`. The application draws the views into the frame over the element boxes before the
+//! document is rendered on top, so the elements have to keep their content transparent.
+
+use std::collections::HashMap;
+
+use gfx;
+use sciter;
+
+use layout::{self, Rect};
+
+/// The tag of the image elements.
+pub const TAG: &'static str = "gfx-image";
+
+/// Shader resource views the document can show, by name.
+pub struct ImageRegistry<R: gfx::Resources> {
+    views: HashMap<String, gfx::handle::ShaderResourceView<R, [f32; 4]>>,
+}
+
+impl<R: gfx::Resources> ImageRegistry<R> {
+    pub fn new() -> Self {
+        ImageRegistry { views: HashMap::new() }
+    }
+
+    /// Registers `view` under `name`, replacing the previous one.
+    pub fn register(&mut self, name: &str, view: gfx::handle::ShaderResourceView<R, [f32; 4]>) {
+        self.views.insert(name.to_string(), view);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&gfx::handle::ShaderResourceView<R, [f32; 4]>> {
+        self.views.get(name)
+    }
+
+    /// The image elements of the document with the views they show.
+    ///
+    /// Elements which aren't laid out (hidden ones, for example) and unknown targets are skipped.
+    pub fn images(&self, api: &sciter::ISciterAPI, root: &sciter::Element)
+        -> Vec<(Rect, gfx::handle::ShaderResourceView<R, [f32; 4]>)>
+    {
+        let elements = match root.find_all(TAG) {
+            Ok(Some(elements)) => elements,
+            _ => return Vec::new(),
+        };
+        elements.iter().filter_map(|element| {
+            let view = match element.get_attribute("target").and_then(|name| self.views.get(&name)) {
+                Some(view) => view.clone(),
+                None => return None,
+            };
+            match layout::element_box(api, element) {
+                Some(rect) if !rect.is_empty() => Some((rect, view)),
+                _ => None,
+            }
+        }).collect()
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generic object inspector.
+//!
+//! Objects implementing `Inspect` expose named properties and named children. `to_value`
+//! turns such a tree into a script value and `set_property` applies an edit coming back
+//! from the script. The script side is `SCRIPT`, which `with_inspector` adds to a facade:
+//! it fills a `<div #inspector>` with an expandable tree, calling the `inspect` and
+//! `setProperty` functions of the window handler.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use sciter::Value;
+
+/// A property value, along with how it can be edited.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    Float { value: f32, min: f32, max: f32 },
+    Int { value: i32, min: i32, max: i32 },
+    Bool(bool),
+    Color([f32; 3]),
+    /// Up to 4 components, each within `min..max`.
+    Vector { value: Vec<f32>, min: f32, max: f32 },
+    /// Read-only, column by column like gfx and cgmath store them. Shown row by row.
+    Matrix([[f32; 4]; 4]),
+    /// Read-only.
+    Text(String),
+}
+
+pub trait Inspect {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        Vec::new()
+    }
+
+    /// Applies an edit, the value being of the same kind as the current one.
+    /// Returns false if there is no such property or it can't be changed.
+    #[allow(unused_variables)]
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        false
+    }
+
+    fn children(&self) -> Vec<(&'static str, &Inspect)> {
+        Vec::new()
+    }
+
+    #[allow(unused_variables)]
+    fn child_mut(&mut self, name: &str) -> Option<&mut Inspect> {
+        None
+    }
+}
+
+/// Parameters shared with the renderer are usually kept in a `Rc<Cell<_>>`.
+impl<T: Inspect + Copy> Inspect for Rc<Cell<T>> {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        self.get().properties()
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        let mut object = self.get();
+        let changed = object.set_property(name, value);
+        self.set(object);
+        changed
+    }
+}
+
+/// Objects that aren't `Copy` are shared through a `Rc<RefCell<_>>`.
+/// Their children can't be borrowed out of the cell, only the properties are exposed.
+impl<T: Inspect> Inspect for Rc<RefCell<T>> {
+    fn properties(&self) -> Vec<(&'static str, Property)> {
+        self.borrow().properties()
+    }
+
+    fn set_property(&mut self, name: &str, value: &Property) -> bool {
+        self.borrow_mut().set_property(name, value)
+    }
+}
+
+fn property_to_value(name: &str, property: &Property) -> Value {
+    let (kind, value, min, max) = match *property {
+        Property::Float { value, min, max } =>
+            ("float", Value::from(value as f64), Value::from(min as f64), Value::from(max as f64)),
+        Property::Int { value, min, max } =>
+            ("int", Value::from(value), Value::from(min), Value::from(max)),
+        Property::Bool(value) => ("bool", Value::from(value), Value::from(false), Value::from(true)),
+        Property::Color(c) => ("color", c.iter().map(|&x| x as f64).collect(), Value::from(0.0), Value::from(1.0)),
+        Property::Vector { ref value, min, max } =>
+            ("vector", value.iter().map(|&x| x as f64).collect(), Value::from(min as f64), Value::from(max as f64)),
+        Property::Matrix(ref m) => {
+            let rows = (0..4).map(|r| m.iter().map(|column| column[r] as f64).collect::<Value>()).collect();
+            ("matrix", rows, Value::from(false), Value::from(false))
+        },
+        Property::Text(ref text) => ("text", Value::from(&text[..]), Value::from(false), Value::from(false)),
+    };
+    vec![Value::from(name), Value::from(kind), value, min, max].into_iter().collect()
+}
+
+/// `[name, [[property, kind, value, min, max], ...], [children]]`
+pub fn to_value(name: &str, object: &Inspect) -> Value {
+    let properties: Value = object.properties().iter().map(|&(name, ref p)| property_to_value(name, p)).collect();
+    let children: Value = object.children().into_iter().map(|(name, child)| to_value(name, child)).collect();
+    vec![Value::from(name), properties, children].into_iter().collect()
+}
+
+// converts a script value into the kind of `current`, within its range
+fn property_from_value(current: &Property, value: &Value) -> Option<Property> {
+    let number = || value.to_float().or_else(|| value.to_int().map(|x| x as f64));
+    match *current {
+        Property::Float { min, max, .. } =>
+            number().map(|x| Property::Float { value: (x as f32).max(min).min(max), min: min, max: max }),
+        Property::Int { min, max, .. } =>
+            number().map(|x| Property::Int { value: (x.round() as i32).max(min).min(max), min: min, max: max }),
+        Property::Bool(_) => value.to_bool().map(Property::Bool),
+        Property::Color(_) if value.len() == 3 => {
+            let channel = |i| (value.get(i).to_float().unwrap_or(0.0) as f32).max(0.0).min(1.0);
+            Some(Property::Color([channel(0), channel(1), channel(2)]))
+        },
+        Property::Vector { value: ref current, min, max } if value.len() == current.len() => {
+            let value = (0 .. current.len()).map(|i| (value.get(i).to_float().unwrap_or(0.0) as f32).max(min).min(max)).collect();
+            Some(Property::Vector { value: value, min: min, max: max })
+        },
+        _ => None,
+    }
+}
+
+/// Sets a property of the object at `path`, a list of child names starting below `root`.
+pub fn set_property(root: &mut Inspect, path: &[String], name: &str, value: &Value) -> bool {
+    let mut object = root;
+    for child in path {
+        object = match {object}.child_mut(child) {
+            Some(child) => child,
+            None => return false,
+        };
+    }
+    let current = match object.properties().into_iter().find(|&(n, _)| n == name) {
+        Some((_, current)) => current,
+        None => return false,
+    };
+    match property_from_value(&current, value) {
+        Some(property) => object.set_property(name, &property),
+        None => false,
+    }
+}
+
+/// Script and styles of the inspector tree.
+pub const SCRIPT: &'static str = include_str!("inspector.htm");
+
+/// Adds the inspector script to a facade, right before its `</head>`.
+pub fn with_inspector(html: &str) -> String {
+    match html.find("</head>") {
+        Some(at) => format!("{}{}{}", &html[..at], SCRIPT, &html[at..]),
+        None => html.to_string(),
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Layout boxes of document elements.
+//!
+//! Locations are relative to the window and ignore CSS transforms, so elements meant to be
+//! tracked by the renderer shouldn't be transformed.
+
+use sciter;
+use sciter::types::RECT;
+
+// `ELEMENT_AREAS` of `SciterGetElementLocation`
+const VIEW_RELATIVE: u32 = 0x04;
+const CONTENT_BOX: u32 = 0x00;
+
+/// A box in window pixels, `x` and `y` being its top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// The box in normalized device coordinates of a `width`x`height` target,
+    /// as `[left, bottom, width, height]`.
+    pub fn to_ndc(&self, width: u16, height: u16) -> [f32; 4] {
+        let (w, h) = (width as f32, height as f32);
+        [
+            2.0 * self.x as f32 / w - 1.0,
+            1.0 - 2.0 * (self.y + self.height) as f32 / h,
+            2.0 * self.width as f32 / w,
+            2.0 * self.height as f32 / h,
+        ]
+    }
+}
+
+/// The content box of `element`, `None` if it can't be located.
+pub fn element_box(api: &sciter::ISciterAPI, element: &sciter::Element) -> Option<Rect> {
+    let mut rc = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+    let result = (api.SciterGetElementLocation)(element.as_ptr(), &mut rc, VIEW_RELATIVE | CONTENT_BOX);
+    if result as i32 != 0 {
+        return None;
+    }
+    Some(Rect {
+        x: rc.left,
+        y: rc.top,
+        width: rc.right - rc.left,
+        height: rc.bottom - rc.top,
+    })
+}
@@ -0,0 +1,97 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pieces shared by the examples.
+
+extern crate gfx;
+extern crate sciter;
+// D3D11 interop, see `offscreen`, `readback` and `timer`
+#[cfg(windows)]
+extern crate gfx_device_dx11;
+#[cfg(windows)]
+extern crate winapi;
+#[cfg(windows)]
+extern crate dxguid;
+
+pub mod inspect;
+pub mod constants;
+pub mod reflect;
+pub mod graph;
+#[cfg(windows)]
+pub mod offscreen;
+#[cfg(windows)]
+pub mod readback;
+#[cfg(windows)]
+pub mod timer;
+pub mod layout;
+pub mod images;
+pub mod viewport;
+
+/// A number passed from the script. Sliders with an integral step send integers,
+/// so both kinds are accepted.
+pub fn to_number(value: &sciter::Value) -> Option<f64> {
+    value.to_float().or_else(|| value.to_int().map(f64::from))
+}
+
+/// The path of a `file://` url, as returned by `view.selectFile`. The `%XX` escapes are
+/// decoded, and on Windows the slash of `file:///C:/...` before the drive is dropped.
+pub fn path_from_url(url: &str) -> String {
+    let path = if url.starts_with("file://") { &url[7..] } else { url };
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3 && bytes[0] == b'/' && bytes[1] < 0x80 && (bytes[1] as char).is_alphabetic() && bytes[2] == b':';
+    let path = if cfg!(windows) && drive { &path[1..] } else { path };
+    percent_decode(path)
+}
+
+/// Decodes the `%XX` escapes of an url, leaving malformed ones as they are.
+pub fn percent_decode(url: &str) -> String {
+    let hex = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let mut decoded = Vec::with_capacity(url.len());
+    let mut rest = url.as_bytes();
+    while !rest.is_empty() {
+        if rest[0] == b'%' && rest.len() >= 3 {
+            if let (Some(high), Some(low)) = (hex(rest[1]), hex(rest[2])) {
+                decoded.push(high << 4 | low);
+                rest = &rest[3..];
+                continue;
+            }
+        }
+        decoded.push(rest[0]);
+        rest = &rest[1..];
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::path_from_url;
+
+    #[test]
+    fn path_from_url_decodes_escapes() {
+        assert_eq!(path_from_url("file:///tmp/my%20ramp.ramp"), "/tmp/my ramp.ramp");
+        assert_eq!(path_from_url("file:///tmp/%C3%A9t%C3%A9"), "/tmp/\u{e9}t\u{e9}");
+        assert_eq!(path_from_url("file:///tmp/100%"), "/tmp/100%");
+        assert_eq!(path_from_url("/tmp/plain"), "/tmp/plain");
+    }
+
+    #[test]
+    fn path_from_url_drops_the_slash_before_a_drive() {
+        let path = path_from_url("file:///C:/Users/me/a%20b.json");
+        if cfg!(windows) {
+            assert_eq!(path, "C:/Users/me/a b.json");
+        } else {
+            assert_eq!(path, "/C:/Users/me/a b.json");
+        }
+    }
+}
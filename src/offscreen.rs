@@ -0,0 +1,138 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Rendering Sciter elements into textures.
+//!
+//! Sciter draws with Direct2D, which needs the DXGI surface of a D3D11 texture created by the
+//! device the window renders with. Only the D3D11 backend provides such textures, with any
+//! other `ElementTexture::new` returns `None`.
+//!
+//! Such elements aren't where the pointer is, so `send_mouse` forwards the mouse input to them.
+
+use std::any::Any;
+use std::ptr;
+use std::os::raw::c_void;
+
+use dxguid::IID_IDXGISurface;
+use gfx;
+use gfx_device_dx11;
+use sciter;
+use winapi::{IDXGISurface, S_OK};
+
+/// A texture Sciter can render into.
+pub struct ElementTexture {
+    surface: *mut IDXGISurface,
+}
+
+impl ElementTexture {
+    /// The texture has to be a render target, see `Factory::create_render_target`.
+    pub fn new<R, T>(texture: &gfx::handle::Texture<R, T>) -> Option<ElementTexture>
+        where R: gfx::Resources
+    {
+        // the resource types of gfx are `Any`
+        let resource = texture.raw().resource() as &Any;
+        let texture = match resource.downcast_ref::<gfx_device_dx11::Texture>() {
+            Some(texture) => texture,
+            None => return None,
+        };
+        let mut surface = ptr::null_mut();
+        let hr = unsafe { (*texture.as_resource()).QueryInterface(&IID_IDXGISurface, &mut surface) };
+        if hr == S_OK {
+            Some(ElementTexture { surface: surface as *mut IDXGISurface })
+        } else {
+            None
+        }
+    }
+
+    /// Renders `element` of the window document, the texture being its border box.
+    pub fn render(&self, api: &sciter::ISciterAPI, hwnd: sciter::types::HWINDOW, element: &sciter::Element) -> bool {
+        (api.SciterRenderOnDirectXTexture)(hwnd, element.as_ptr(), self.surface as *mut _) != 0
+    }
+}
+
+impl Drop for ElementTexture {
+    fn drop(&mut self) {
+        unsafe { (*self.surface).Release() };
+    }
+}
+
+/// The mouse events `send_mouse` forwards, `MOUSE_EVENTS` of the Sciter API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseEvent {
+    Enter = 0,
+    Leave = 1,
+    Move = 2,
+    Up = 3,
+    Down = 4,
+}
+
+impl MouseEvent {
+    pub fn from_name(name: &str) -> Option<MouseEvent> {
+        match name {
+            "enter" => Some(MouseEvent::Enter),
+            "leave" => Some(MouseEvent::Leave),
+            "move" => Some(MouseEvent::Move),
+            "up" => Some(MouseEvent::Up),
+            "down" => Some(MouseEvent::Down),
+            _ => None,
+        }
+    }
+}
+
+// `MOUSE_PARAMS` of the Sciter API
+#[repr(C)]
+struct MouseParams {
+    cmd: u32,
+    target: sciter::HELEMENT,
+    pos: [i32; 2],
+    pos_view: [i32; 2],
+    button_state: u32,
+    alt_state: u32,
+    cursor_type: u32,
+    is_on_icon: i32,
+    dragging: sciter::HELEMENT,
+    dragging_mode: u32,
+}
+
+/// Sends a mouse event to the element with the script `uid`, sinking and bubbling it like the
+/// window does. `pos` is relative to the element and `pos_view` to the view, the main button
+/// is down or not.
+pub fn send_mouse(api: &sciter::ISciterAPI, hwnd: sciter::types::HWINDOW, uid: u32,
+                  event: MouseEvent, pos: [i32; 2], pos_view: [i32; 2], pressed: bool) -> bool {
+    // HANDLE_MOUSE of `EVENT_GROUPS`, MAIN_MOUSE_BUTTON of `MOUSE_BUTTONS`
+    const HANDLE_MOUSE: u32 = 1;
+    const MAIN_MOUSE_BUTTON: u32 = 1;
+
+    let mut target = ptr::null_mut();
+    let result = (api.SciterGetElementByUID)(hwnd, uid, &mut target);
+    if result as i32 != 0 || target.is_null() {
+        return false;
+    }
+    let mut params = MouseParams {
+        cmd: event as u32,
+        target: target,
+        pos: pos,
+        pos_view: pos_view,
+        button_state: if pressed { MAIN_MOUSE_BUTTON } else { 0 },
+        alt_state: 0,
+        cursor_type: 0,
+        is_on_icon: 0,
+        dragging: ptr::null_mut(),
+        dragging_mode: 0,
+    };
+    let mut processed = 0;
+    (api.SciterTraverseUIEvent)(HANDLE_MOUSE, &mut params as *mut MouseParams as *mut c_void as *mut _, &mut processed);
+    processed != 0
+}
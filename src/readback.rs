@@ -0,0 +1,119 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading textures back from the GPU.
+//!
+//! This gfx version can't copy a texture to memory. With the D3D11 backend the texture is
+//! copied into a staging texture of the same device and mapped, with any other
+//! `TextureReader::new` returns `None`.
+
+use std::any::Any;
+use std::{mem, ptr, slice};
+
+use dxguid::IID_ID3D11Texture2D;
+use gfx;
+use gfx_device_dx11;
+use winapi::{ID3D11Device, ID3D11DeviceContext, ID3D11Resource, ID3D11Texture2D};
+use winapi::{D3D11_CPU_ACCESS_READ, D3D11_MAP_READ, D3D11_MAPPED_SUBRESOURCE};
+use winapi::{D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, S_OK};
+use winapi::{DXGI_FORMAT_R8G8B8A8_TYPELESS, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB};
+
+/// A 2D texture which can be read back.
+pub struct TextureReader {
+    texture: *mut ID3D11Texture2D,
+}
+
+impl TextureReader {
+    pub fn new<R, T>(texture: &gfx::handle::Texture<R, T>) -> Option<TextureReader>
+        where R: gfx::Resources
+    {
+        // the resource types of gfx are `Any`
+        let resource = texture.raw().resource() as &Any;
+        let texture = match resource.downcast_ref::<gfx_device_dx11::Texture>() {
+            Some(texture) => texture,
+            None => return None,
+        };
+        let mut texture2d = ptr::null_mut();
+        let hr = unsafe { (*texture.as_resource()).QueryInterface(&IID_ID3D11Texture2D, &mut texture2d) };
+        if hr == S_OK {
+            Some(TextureReader { texture: texture2d as *mut ID3D11Texture2D })
+        } else {
+            None
+        }
+    }
+
+    /// The first level of an 8 bit per channel RGBA texture, as `(width, height, pixels)`.
+    ///
+    /// The copy runs after the commands already submitted to the device, so the texture holds
+    /// what the last flushed frame rendered into it. Textures of any other format are an error.
+    pub fn read_rgba8(&self) -> Result<(u32, u32, Vec<u8>), String> {
+        unsafe {
+            let mut desc: D3D11_TEXTURE2D_DESC = mem::zeroed();
+            (*self.texture).GetDesc(&mut desc);
+            // gfx may create the storage typeless and only type the views
+            let format = desc.Format;
+            if format != DXGI_FORMAT_R8G8B8A8_UNORM && format != DXGI_FORMAT_R8G8B8A8_UNORM_SRGB &&
+               format != DXGI_FORMAT_R8G8B8A8_TYPELESS {
+                return Err(format!("the texture isn't RGBA8 (DXGI format {})", format.0));
+            }
+            desc.MipLevels = 1;
+            desc.ArraySize = 1;
+            desc.Usage = D3D11_USAGE_STAGING;
+            desc.BindFlags = 0;
+            desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0;
+            desc.MiscFlags = 0;
+
+            let mut device: *mut ID3D11Device = ptr::null_mut();
+            (*self.texture).GetDevice(&mut device);
+            let mut context: *mut ID3D11DeviceContext = ptr::null_mut();
+            (*device).GetImmediateContext(&mut context);
+
+            let mut staging: *mut ID3D11Texture2D = ptr::null_mut();
+            let hr = (*device).CreateTexture2D(&desc, ptr::null(), &mut staging);
+            let result = if hr != S_OK {
+                Err(format!("unable to create a staging texture: {:#x}", hr))
+            } else {
+                let source = self.texture as *mut ID3D11Resource;
+                let target = staging as *mut ID3D11Resource;
+                (*context).CopySubresourceRegion(target, 0, 0, 0, 0, source, 0, ptr::null());
+                let mut mapped: D3D11_MAPPED_SUBRESOURCE = mem::zeroed();
+                let hr = (*context).Map(target, 0, D3D11_MAP_READ, 0, &mut mapped);
+                let result = if hr != S_OK {
+                    Err(format!("unable to map the staging texture: {:#x}", hr))
+                } else {
+                    let (width, height) = (desc.Width as usize, desc.Height as usize);
+                    let pitch = mapped.RowPitch as usize;
+                    let data = slice::from_raw_parts(mapped.pData as *const u8, pitch * height);
+                    let mut pixels = Vec::with_capacity(4 * width * height);
+                    for row in data.chunks(pitch) {
+                        pixels.extend_from_slice(&row[.. 4 * width]);
+                    }
+                    (*context).Unmap(target, 0);
+                    Ok((desc.Width, desc.Height, pixels))
+                };
+                (*staging).Release();
+                result
+            };
+            (*context).Release();
+            (*device).Release();
+            result
+        }
+    }
+}
+
+impl Drop for TextureReader {
+    fn drop(&mut self) {
+        unsafe { (*self.texture).Release() };
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Shader reflection of the pipelines.
+//!
+//! `reflect_pipeline!` takes the same definition as `gfx_pipeline!` and lets the `Init` of the
+//! pipeline list what it declares, and `reflect_vertex_struct!` does the same for the attributes
+//! of `gfx_vertex_struct!`. `create_pipeline` links the program itself to keep the
+//! reflection gfx does while linking, and compares it against the declaration: anything the
+//! pipeline declares that the shaders don't use is flagged.
+
+use gfx;
+use gfx::shade::ProgramInfo;
+use gfx::state::{Blend, ColorMask, CullFace, Depth, Rasterizer, Stencil};
+use sciter::Value;
+
+/// What a pipeline component is bound to in the shaders.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    /// Vertex buffers bind their attributes by the names of the vertex struct, listed here.
+    Vertex(Vec<&'static str>),
+    /// Constant buffers, globals, textures and render targets bind by name.
+    Named(String),
+    /// The depth target is used by the fixed function depth test.
+    Depth,
+}
+
+/// The `Init` value of a component, as found in `gfx_pipeline!`.
+pub trait ComponentInit {
+    fn binding(&self) -> Binding;
+}
+
+impl ComponentInit for () {
+    fn binding(&self) -> Binding {
+        Binding::Vertex(Vec::new())
+    }
+}
+
+impl<'a> ComponentInit for &'a str {
+    fn binding(&self) -> Binding {
+        Binding::Named(self.to_string())
+    }
+}
+
+impl<'a> ComponentInit for (&'a str, ColorMask, Blend) {
+    fn binding(&self) -> Binding {
+        Binding::Named(self.0.to_string())
+    }
+}
+
+impl ComponentInit for Depth {
+    fn binding(&self) -> Binding {
+        Binding::Depth
+    }
+}
+
+impl ComponentInit for (Depth, Stencil) {
+    fn binding(&self) -> Binding {
+        Binding::Depth
+    }
+}
+
+/// Implemented by `reflect_vertex_struct!` for the vertex struct.
+pub trait Attributes {
+    /// The shader names of the attributes.
+    fn attributes() -> Vec<&'static str>;
+}
+
+/// The type of a component, as found in `gfx_pipeline!`. Only vertex buffers have attributes.
+/// Implemented for the component types the examples use.
+pub trait Component {
+    fn attributes() -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+impl<T: Attributes> Component for gfx::VertexBuffer<T> {
+    fn attributes() -> Vec<&'static str> {
+        T::attributes()
+    }
+}
+
+impl<T: gfx::pso::buffer::Structure<gfx::shade::ConstFormat>> Component for gfx::ConstantBuffer<T> {}
+impl<T> Component for gfx::TextureSampler<T> {}
+impl<T: gfx::format::RenderFormat> Component for gfx::RenderTarget<T> {}
+impl<T: gfx::format::BlendFormat> Component for gfx::BlendTarget<T> {}
+impl<T: gfx::format::DepthFormat> Component for gfx::DepthTarget<T> {}
+
+/// The binding of a component of type `T`, along with its attributes.
+pub fn binding<T: Component, I: ComponentInit>(init: &I) -> Binding {
+    match init.binding() {
+        Binding::Vertex(_) => Binding::Vertex(T::attributes()),
+        binding => binding,
+    }
+}
+
+/// Defines a vertex struct with `gfx_vertex_struct!` and implements `Attributes` for it.
+#[macro_export]
+macro_rules! reflect_vertex_struct {
+    ($root:ident { $( $field:ident : $ty:ty = $name:expr, )* }) => {
+        gfx_vertex_struct!( $root {
+            $( $field: $ty = $name, )*
+        });
+
+        impl $crate::reflect::Attributes for $root {
+            fn attributes() -> Vec<&'static str> {
+                vec![ $( $name, )* ]
+            }
+        }
+    }
+}
+
+/// Implemented by `reflect_pipeline!` for the `Init` of the pipeline.
+pub trait Declared {
+    /// The components in the order of the definition.
+    fn declared(&self) -> Vec<(&'static str, Binding)>;
+}
+
+/// Defines a pipeline with `gfx_pipeline!` and implements `Declared` for its `Init`.
+#[macro_export]
+macro_rules! reflect_pipeline {
+    ($module:ident { $( $field:ident : $ty:ty = $value:expr, )* }) => {
+        gfx_pipeline!( $module {
+            $( $field: $ty = $value, )*
+        });
+
+        impl<'a> $crate::reflect::Declared for $module::Init<'a> {
+            fn declared(&self) -> Vec<(&'static str, $crate::reflect::Binding)> {
+                vec![ $( (stringify!($field), $crate::reflect::binding::<$ty, _>(&self.$field)), )* ]
+            }
+        }
+    }
+}
+
+/// The reflection of one pipeline.
+#[derive(Clone, Debug)]
+pub struct Reflection {
+    pub name: String,
+    pub info: ProgramInfo,
+    pub declared: Vec<(&'static str, Binding)>,
+}
+
+impl Reflection {
+    pub fn new<I: Declared>(name: &str, info: &ProgramInfo, init: &I) -> Reflection {
+        Reflection {
+            name: name.to_string(),
+            info: info.clone(),
+            declared: init.declared(),
+        }
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        let info = &self.info;
+        info.vertex_attributes.iter().any(|v| v.name == name) ||
+        info.globals.iter().any(|v| v.name == name) ||
+        info.constant_buffers.iter().any(|v| v.name == name) ||
+        info.textures.iter().any(|v| v.name == name) ||
+        info.samplers.iter().any(|v| v.name == name) ||
+        info.outputs.iter().any(|v| v.name == name)
+    }
+
+    /// The declared components the shaders don't use, along with their shader names.
+    ///
+    /// Vertex buffers are flagged once for each attribute of their struct the shaders don't
+    /// read. Some GL versions don't report the fragment outputs, the targets are never flagged then.
+    pub fn unused(&self) -> Vec<(&'static str, String)> {
+        let mut unused = Vec::new();
+        for &(field, ref binding) in &self.declared {
+            match *binding {
+                Binding::Vertex(ref attributes) => {
+                    for name in attributes.iter().filter(|name| !self.uses(name)) {
+                        unused.push((field, name.to_string()));
+                    }
+                },
+                Binding::Named(ref name) if !self.uses(name) => {
+                    let target = name.starts_with("Target");
+                    if !target || self.info.knows_outputs {
+                        unused.push((field, name.clone()));
+                    }
+                },
+                _ => (),
+            }
+        }
+        unused
+    }
+
+    /// `[name, [[section, [[name, details], ...]], ...], [[field, shader name], ...]]`,
+    /// the last list holding what `unused` reports.
+    pub fn to_value(&self) -> Value {
+        fn section(title: &str, items: Vec<(String, String)>) -> Value {
+            let items: Value = items.into_iter().map(|(name, details)| {
+                vec![Value::from(&name[..]), Value::from(&details[..])].into_iter().collect::<Value>()
+            }).collect();
+            vec![Value::from(title), items].into_iter().collect()
+        }
+
+        let info = &self.info;
+        let sections: Value = vec![
+            section("vertex attributes", info.vertex_attributes.iter().map(|v|
+                (v.name.clone(), format!("slot {}, {:?} {:?}", v.slot, v.base_type, v.container))).collect()),
+            section("constant buffers", info.constant_buffers.iter().map(|v|
+                (v.name.clone(), format!("slot {}, {} bytes, {} elements", v.slot, v.size, v.elements.len()))).collect()),
+            section("globals", info.globals.iter().map(|v|
+                (v.name.clone(), format!("{:?} {:?}", v.base_type, v.container))).collect()),
+            section("textures", info.textures.iter().map(|v|
+                (v.name.clone(), format!("slot {}, {:?}", v.slot, v.ty))).collect()),
+            section("samplers", info.samplers.iter().map(|v|
+                (v.name.clone(), format!("slot {}", v.slot))).collect()),
+            section("render targets", info.outputs.iter().map(|v|
+                (v.name.clone(), format!("slot {}, {:?} {:?}", v.slot, v.base_type, v.container))).collect()),
+        ].into_iter().collect();
+        let unused: Value = self.unused().into_iter().map(|(field, name)| {
+            vec![Value::from(field), Value::from(&name[..])].into_iter().collect::<Value>()
+        }).collect();
+        vec![Value::from(&self.name[..]), sections, unused].into_iter().collect()
+    }
+}
+
+/// Like `create_pipeline_simple`, returning the reflection of the pipeline as well.
+pub fn create_pipeline<R, F, I>(factory: &mut F, name: &str, vs: &[u8], ps: &[u8], cull: CullFace, init: I)
+                                -> Result<(gfx::PipelineState<R, I::Meta>, Reflection), String>
+    where R: gfx::Resources, F: gfx::Factory<R>, I: gfx::pso::PipelineInit + Declared
+{
+    use gfx::traits::FactoryExt;
+
+    let program = try!(factory.link_program(vs, ps).map_err(|e| format!("{}: {:?}", name, e)));
+    let reflection = Reflection::new(name, program.get_info(), &init);
+    let rasterizer = Rasterizer { cull_face: cull, .. Rasterizer::new_fill() };
+    let pso = try!(factory.create_pipeline_from_program(&program, gfx::Primitive::TriangleList, rasterizer, init)
+                          .map_err(|e| format!("{}: {:?}", name, e)));
+    Ok((pso, reflection))
+}
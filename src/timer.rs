@@ -0,0 +1,148 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measuring GPU time.
+//!
+//! gfx has no queries, so the D3D11 timestamp queries are issued on the immediate context
+//! directly, with any other backend `GpuTimer::new` returns `None`. They bracket whatever the
+//! device executes between `begin` and `end`: gfx_app flushes the encoder after `render_pre`
+//! and after `render`, so calling `begin` in `render_pre` and `end` in `render` measures the
+//! commands encoded in `render_pre`.
+
+use std::any::Any;
+use std::{mem, ptr};
+
+use gfx;
+use gfx_device_dx11;
+use winapi::{c_void, ID3D11Asynchronous, ID3D11Device, ID3D11DeviceContext, ID3D11Query, S_OK};
+use winapi::{D3D11_QUERY_DESC, D3D11_QUERY_DATA_TIMESTAMP_DISJOINT, D3D11_ASYNC_GETDATA_DONOTFLUSH};
+use winapi::{D3D11_QUERY, D3D11_QUERY_TIMESTAMP, D3D11_QUERY_TIMESTAMP_DISJOINT};
+
+// measures in flight, the results are read a few frames later without waiting
+const LATENCY: usize = 3;
+
+struct Queries {
+    disjoint: *mut ID3D11Query,
+    start: *mut ID3D11Query,
+    end: *mut ID3D11Query,
+    tag: usize,
+    // from `end` until the result is read
+    pending: bool,
+}
+
+pub struct GpuTimer {
+    context: *mut ID3D11DeviceContext,
+    queries: Vec<Queries>,
+    current: usize,
+}
+
+unsafe fn create_query(device: *mut ID3D11Device, kind: D3D11_QUERY) -> *mut ID3D11Query {
+    let desc = D3D11_QUERY_DESC { Query: kind, MiscFlags: 0 };
+    let mut query = ptr::null_mut();
+    (*device).CreateQuery(&desc, &mut query);
+    query
+}
+
+impl GpuTimer {
+    /// Uses the device `texture` belongs to.
+    pub fn new<R, T>(texture: &gfx::handle::Texture<R, T>) -> Option<GpuTimer>
+        where R: gfx::Resources
+    {
+        // the resource types of gfx are `Any`
+        let resource = texture.raw().resource() as &Any;
+        let texture = match resource.downcast_ref::<gfx_device_dx11::Texture>() {
+            Some(texture) => texture,
+            None => return None,
+        };
+        unsafe {
+            let mut device: *mut ID3D11Device = ptr::null_mut();
+            (*texture.as_resource()).GetDevice(&mut device);
+            let mut context = ptr::null_mut();
+            (*device).GetImmediateContext(&mut context);
+            let queries = (0..LATENCY).map(|_| Queries {
+                disjoint: create_query(device, D3D11_QUERY_TIMESTAMP_DISJOINT),
+                start: create_query(device, D3D11_QUERY_TIMESTAMP),
+                end: create_query(device, D3D11_QUERY_TIMESTAMP),
+                tag: 0,
+                pending: false,
+            }).collect();
+            (*device).Release();
+            Some(GpuTimer { context: context, queries: queries, current: 0 })
+        }
+    }
+
+    /// `tag` comes back with the result, to tell what was measured.
+    pub fn begin(&mut self, tag: usize) {
+        let q = &mut self.queries[self.current];
+        q.tag = tag;
+        unsafe {
+            (*self.context).Begin(q.disjoint as *mut ID3D11Asynchronous);
+            (*self.context).End(q.start as *mut ID3D11Asynchronous);
+        }
+    }
+
+    pub fn end(&mut self) {
+        {
+            let q = &mut self.queries[self.current];
+            unsafe {
+                (*self.context).End(q.end as *mut ID3D11Asynchronous);
+                (*self.context).End(q.disjoint as *mut ID3D11Asynchronous);
+            }
+            q.pending = true;
+        }
+        self.current = (self.current + 1) % LATENCY;
+    }
+
+    /// The tag and milliseconds of the oldest finished measure, `None` if it isn't available
+    /// yet or the GPU clock wasn't reliable meanwhile.
+    pub fn poll(&mut self) -> Option<(usize, f64)> {
+        // the next slot to be reused holds the oldest measure
+        let context = self.context;
+        let q = &mut self.queries[self.current];
+        if !q.pending {
+            return None;
+        }
+        unsafe {
+            let get = |query: *mut ID3D11Query, data: *mut c_void, size: usize| {
+                (*context).GetData(query as *mut ID3D11Asynchronous, data, size as u32,
+                                   D3D11_ASYNC_GETDATA_DONOTFLUSH.0) == S_OK
+            };
+            let mut disjoint: D3D11_QUERY_DATA_TIMESTAMP_DISJOINT = mem::zeroed();
+            let (mut start, mut end) = (0u64, 0u64);
+            if !get(q.disjoint, &mut disjoint as *mut _ as *mut c_void, mem::size_of_val(&disjoint)) ||
+               !get(q.start, &mut start as *mut _ as *mut c_void, 8) ||
+               !get(q.end, &mut end as *mut _ as *mut c_void, 8) {
+                return None;
+            }
+            q.pending = false;
+            if disjoint.Disjoint != 0 || disjoint.Frequency == 0 {
+                return None;
+            }
+            Some((q.tag, (end - start) as f64 * 1000.0 / disjoint.Frequency as f64))
+        }
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            for q in &self.queries {
+                (*q.disjoint).Release();
+                (*q.start).Release();
+                (*q.end).Release();
+            }
+            (*self.context).Release();
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! 3D viewports bound to document elements.
+//!
+//! An element with the `viewport` attribute marks where the scene is drawn, e.g.
+//! `<section #viewport viewport>`. The boxes are looked up again every frame, so the viewports
+//! follow layout changes. gfx sets the viewport to the whole render target, a `Viewport` gives
+//! the transform to apply after the projection instead, along with the scissor rectangle
+//! clipping the draws to the box.
+
+use gfx;
+use sciter;
+
+use layout::{self, Rect};
+
+/// Marks the viewport elements.
+pub const ATTRIBUTE: &'static str = "viewport";
+
+pub struct Viewport {
+    pub rect: Rect,
+    /// `None` for the fallback viewport covering the whole target.
+    pub element: Option<sciter::Element>,
+}
+
+impl Viewport {
+    /// A viewport covering the whole `width`x`height` target.
+    pub fn full(width: u16, height: u16) -> Viewport {
+        Viewport {
+            rect: Rect { x: 0, y: 0, width: width as i32, height: height as i32 },
+            element: None,
+        }
+    }
+
+    /// A numeric attribute of the element, to pick a camera for example.
+    pub fn number(&self, name: &str) -> Option<f32> {
+        self.element.as_ref()
+            .and_then(|element| element.get_attribute(name))
+            .and_then(|value| value.trim().parse().ok())
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.rect.width as f32 / self.rect.height as f32
+    }
+
+    /// Maps normalized device coordinates onto the box in a `width`x`height` target,
+    /// column major.
+    pub fn transform(&self, width: u16, height: u16) -> [[f32; 4]; 4] {
+        let ndc = self.rect.to_ndc(width, height);
+        let (sx, sy) = (0.5 * ndc[2], 0.5 * ndc[3]);
+        [
+            [ sx, 0.0, 0.0, 0.0],
+            [0.0,  sy, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [ndc[0] + sx, ndc[1] + sy, 0.0, 1.0],
+        ]
+    }
+
+    /// The box clipped to a `width`x`height` target. D3D11 counts the rows from the top like
+    /// the layout does, GL from the bottom; `from_bottom` flips them for the latter.
+    pub fn scissor(&self, width: u16, height: u16, from_bottom: bool) -> gfx::target::Rect {
+        let clamp = |v: i32, max: u16| v.max(0).min(max as i32) as u16;
+        let (left, top) = (clamp(self.rect.x, width), clamp(self.rect.y, height));
+        let right = clamp(self.rect.x + self.rect.width, width);
+        let bottom = clamp(self.rect.y + self.rect.height, height);
+        let y = if from_bottom { height - bottom } else { top };
+        gfx::target::Rect { x: left, y: y, w: right - left, h: bottom - top }
+    }
+}
+
+/// The viewport elements of the document in document order, so the later ones are drawn over
+/// the earlier. Elements which aren't laid out are skipped.
+pub fn find(api: &sciter::ISciterAPI, root: &sciter::Element) -> Vec<Viewport> {
+    let elements = match root.find_all(&format!("[{}]", ATTRIBUTE)) {
+        Ok(Some(elements)) => elements,
+        _ => return Vec::new(),
+    };
+    elements.into_iter().filter_map(|element| {
+        match layout::element_box(api, &element) {
+            Some(rect) if !rect.is_empty() => Some(Viewport { rect: rect, element: Some(element) }),
+            _ => None,
+        }
+    }).collect()
+}
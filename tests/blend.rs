@@ -0,0 +1,212 @@
+// Copyright 2016 The Gfx-rs Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Checks the blend example shaders against the CPU reference of the equations.
+//
+// The GPU side renders with the GLSL 1.50 shader into an offscreen render target and reads
+// that back, preferably on a software rasterizer (Mesa llvmpipe) so the results don't depend
+// on the driver. The GL context comes from a hidden window, which needs a display, so the test
+// is ignored by default; run it with `cargo test -- --ignored`.
+// Only that shader is covered: the GLSL 1.20 and HLSL versions repeat the same equations
+// and have to be kept in sync by hand, the HLSL one can't run outside Windows.
+
+#[macro_use]
+extern crate gfx;
+extern crate gfx_window_glutin;
+extern crate gfx_device_gl;
+extern crate gfx_gl;
+extern crate glutin;
+extern crate image;
+
+#[path = "../examples/blend/reference.rs"]
+mod reference;
+
+use gfx::traits::FactoryExt;
+use gfx::Device;
+use image::RgbaImage;
+
+use reference::BLENDS;
+
+pub use gfx::format::{Rgba8, DepthStencil};
+
+// largest difference allowed per channel, in 1/255 steps
+const TOLERANCE: i32 = 2;
+
+gfx_vertex_struct!( Vertex {
+    pos: [f32; 2] = "a_Pos",
+    uv: [f32; 2] = "a_Uv",
+});
+
+// the example pipeline, except for a linear target so the values can be compared directly
+gfx_pipeline!( pipe {
+    vbuf: gfx::VertexBuffer<Vertex> = (),
+    lena: gfx::TextureSampler<[f32; 4]> = "t_Lena",
+    tint: gfx::TextureSampler<[f32; 4]> = "t_Tint",
+    blend: gfx::Global<i32> = "i_Blend",
+    opacity: gfx::Global<f32> = "f_Opacity",
+    use_mask: gfx::Global<i32> = "i_Mask",
+    mask: gfx::TextureSampler<[f32; 4]> = "t_Mask",
+    out: gfx::RenderTarget<Rgba8> = "Target0",
+});
+
+fn load_image(data: &[u8]) -> RgbaImage {
+    use std::io::Cursor;
+    image::load(Cursor::new(data), image::PNG).unwrap().to_rgba()
+}
+
+#[test]
+fn reference_identities() {
+    let i = [0.25, 0.5, 0.75];
+    let index = |name: &str| BLENDS.iter().position(|&b| b == name).unwrap();
+    assert_eq!(reference::blend_pixel(index("Screen"), i, [0.0; 3]), i);
+    assert_eq!(reference::blend_pixel(index("Multiply"), i, [1.0; 3]), i);
+    assert_eq!(reference::blend_pixel(index("Burn"), i, [1.0; 3]), i);
+    assert_eq!(reference::blend_pixel(index("Difference"), i, i), [0.0; 3]);
+    assert_eq!(reference::blend_pixel(index("Grain Merge"), i, [0.5; 3]), i);
+    assert_eq!(reference::blend_pixel(index("Hard light"), i, [0.5; 3]), i);
+    // a gray layer has no hue and no saturation
+    assert_eq!(reference::blend_pixel(index("Hue"), [0.2, 0.4, 0.6], [0.5; 3]), [0.2, 0.4, 0.6]);
+    let gray = reference::blend_pixel(index("Saturation"), [0.2, 0.4, 0.6], [0.5; 3]);
+    assert_eq!(gray, [0.6; 3]);
+}
+
+#[test]
+#[ignore]
+fn gpu_matches_reference() {
+    use std::env;
+    env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+
+    let lena = load_image(&include_bytes!("../examples/blend/image/lena.png")[..]);
+    let tint = load_image(&include_bytes!("../examples/blend/image/tint.png")[..]);
+    let (width, height) = lena.dimensions();
+
+    // the window only provides the context, its framebuffer contents are undefined while hidden
+    let builder = glutin::WindowBuilder::new()
+        .with_dimensions(1, 1)
+        .with_visibility(false)
+        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)));
+    let (window, mut device, mut factory, _, _) =
+        gfx_window_glutin::init::<Rgba8, DepthStencil>(builder);
+    let mut encoder: gfx::Encoder<_, _> = factory.create_command_buffer().into();
+    let gl = gfx_gl::Gl::load_with(|s| window.get_proc_address(s) as *const _);
+
+    let (target, _, target_view) = factory.create_render_target::<Rgba8>(width as gfx::tex::Size, height as gfx::tex::Size).unwrap();
+    let texture = match *target.raw().resource() {
+        gfx_device_gl::NewTexture::Texture(name) => name,
+        gfx_device_gl::NewTexture::Surface(_) => panic!("the render target has no texture"),
+    };
+    // a framebuffer of our own to read the target through
+    let mut read_fbo = 0;
+    unsafe {
+        gl.GenFramebuffers(1, &mut read_fbo);
+    }
+
+    let vertex_data = [
+        Vertex { pos: [-1.0, -1.0], uv: [0.0, 1.0] },
+        Vertex { pos: [ 1.0, -1.0], uv: [1.0, 1.0] },
+        Vertex { pos: [ 1.0,  1.0], uv: [1.0, 0.0] },
+
+        Vertex { pos: [-1.0, -1.0], uv: [0.0, 1.0] },
+        Vertex { pos: [ 1.0,  1.0], uv: [1.0, 0.0] },
+        Vertex { pos: [-1.0,  1.0], uv: [0.0, 0.0] },
+    ];
+    let (vbuf, slice) = factory.create_vertex_buffer(&vertex_data);
+
+    let kind = gfx::tex::Kind::D2(width as gfx::tex::Size, height as gfx::tex::Size, gfx::tex::AaMode::Single);
+    let (_, lena_view) = factory.create_texture_const_u8::<Rgba8>(kind, &[&lena]).unwrap();
+    let (_, tint_view) = factory.create_texture_const_u8::<Rgba8>(kind, &[&tint]).unwrap();
+    let sampler = factory.create_sampler_linear();
+
+    let pso = factory.create_pipeline_simple(
+        include_bytes!("../examples/blend/shader/blend_150.glslv"),
+        include_bytes!("../examples/blend/shader/blend_150.glslf"),
+        gfx::state::CullFace::Nothing,
+        pipe::new()
+    ).unwrap();
+    let mut data = pipe::Data {
+        vbuf: vbuf,
+        lena: (lena_view.clone(), sampler.clone()),
+        tint: (tint_view, sampler.clone()),
+        blend: 0,
+        opacity: 1.0,
+        use_mask: 0,
+        // any grayscale image does as a mask
+        mask: (lena_view, sampler),
+        out: target_view,
+    };
+
+    // every mode at full opacity, then a partial and masked one
+    let mut cases: Vec<(usize, f32, bool)> = (0..BLENDS.len()).map(|mode| (mode, 1.0, false)).collect();
+    cases.push((BLENDS.iter().position(|&b| b == "Overlay").unwrap(), 0.5, false));
+    cases.push((BLENDS.iter().position(|&b| b == "Multiply").unwrap(), 0.75, true));
+
+    let mut failures = Vec::new();
+    for &(mode, opacity, masked) in &cases {
+        data.blend = mode as i32;
+        data.opacity = opacity;
+        data.use_mask = masked as i32;
+        encoder.clear(&data.out, [0.0; 4]);
+        encoder.draw(&slice, &pso, &data);
+        encoder.flush(&mut device);
+
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            gl.Finish();
+            gl.BindFramebuffer(gfx_gl::READ_FRAMEBUFFER, read_fbo);
+            gl.FramebufferTexture2D(gfx_gl::READ_FRAMEBUFFER, gfx_gl::COLOR_ATTACHMENT0, gfx_gl::TEXTURE_2D, texture, 0);
+            gl.ReadBuffer(gfx_gl::COLOR_ATTACHMENT0);
+            gl.PixelStorei(gfx_gl::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(0, 0, width as i32, height as i32, gfx_gl::RGBA, gfx_gl::UNSIGNED_BYTE,
+                          pixels.as_mut_ptr() as *mut _);
+        }
+        device.cleanup();
+
+        let mask = if masked { Some(&lena) } else { None };
+        let expected = reference::blend(mode, &lena, &tint, opacity, mask);
+        let mut mismatches = 0;
+        let mut worst = 0;
+        for y in 0..height {
+            // GL rows go bottom up, the first row of the image is at the top
+            let row = (height - 1 - y) * width * 4;
+            for x in 0..width {
+                let l = lena.get_pixel(x, y)[0] as f32 / 255.0;
+                let t = tint.get_pixel(x, y);
+                let m = [t[0] as f32 / 255.0, t[1] as f32 / 255.0, t[2] as f32 / 255.0];
+                let exact = reference::blend_pixel(mode, [l, l, l], m);
+                for c in 0..3 {
+                    // 0/0 in the division modes is undefined on the GPU
+                    if exact[c].is_nan() {
+                        continue;
+                    }
+                    let gpu = pixels[(row + x * 4) as usize + c] as i32;
+                    let diff = (gpu - expected.get_pixel(x, y)[c] as i32).abs();
+                    worst = worst.max(diff);
+                    if diff > TOLERANCE {
+                        mismatches += 1;
+                    }
+                }
+            }
+        }
+        if mismatches > 0 {
+            failures.push(format!("{} (opacity {}, mask {}): {} channels off, by up to {}/255",
+                                  BLENDS[mode], opacity, masked, mismatches, worst));
+        }
+    }
+
+    unsafe {
+        gl.BindFramebuffer(gfx_gl::READ_FRAMEBUFFER, 0);
+        gl.DeleteFramebuffers(1, &read_fbo);
+    }
+    assert!(failures.is_empty(), "GPU and CPU disagree:\n{}", failures.join("\n"));
+}